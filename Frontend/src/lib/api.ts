@@ -0,0 +1,85 @@
+import { keccak256, stringToBytes, type Address, type Hex } from 'viem';
+
+export const API_URL = process.env.NEXT_PUBLIC_API_URL || 'http://localhost:3001';
+
+// Must match SIWE_CHAIN_ID of the backend.
+const CHAIN_ID = Number(process.env.NEXT_PUBLIC_SIWE_CHAIN_ID || 1);
+
+// How long a signed action stays valid, well below the backend's limit.
+const ACTION_TTL_SECONDS = 300;
+
+export type MarketTable = 'oil_token' | 'tokenization' | 'comment';
+
+const MARKET_ACTION_TYPES = {
+  MarketAction: [
+    { name: 'actor', type: 'address' },
+    { name: 'table', type: 'string' },
+    { name: 'action', type: 'string' },
+    { name: 'content', type: 'bytes32' },
+    { name: 'nonce', type: 'string' },
+    { name: 'deadline', type: 'uint256' },
+  ],
+} as const;
+
+// `signTypedDataAsync` of wagmi's useSignTypedData.
+type SignMarketAction = (args: {
+  domain: { name: string; version: string; chainId: number };
+  types: typeof MARKET_ACTION_TYPES;
+  primaryType: 'MarketAction';
+  message: {
+    actor: Address;
+    table: string;
+    action: string;
+    content: Hex;
+    nonce: string;
+    deadline: bigint;
+  };
+}) => Promise<Hex>;
+
+function actionNonce(): string {
+  const bytes = crypto.getRandomValues(new Uint8Array(16));
+  return Array.from(bytes, (b) => b.toString(16).padStart(2, '0')).join('');
+}
+
+// POSTs a marketplace write signed as an EIP-712 MarketAction by the logged in wallet.
+// The signature covers the exact body sent, so it is serialized once here.
+export async function signedPost(
+  path: string,
+  body: unknown,
+  { token, actor, table, action = 'CREATE', signTypedData }: {
+    token: string;
+    actor: Address;
+    table: MarketTable;
+    action?: 'CREATE' | 'UPDATE';
+    signTypedData: SignMarketAction;
+  },
+): Promise<Response> {
+  const payload = JSON.stringify(body ?? {});
+  const nonce = actionNonce();
+  const deadline = Math.floor(Date.now() / 1000) + ACTION_TTL_SECONDS;
+  const signature = await signTypedData({
+    domain: { name: 'Oil Tokenization Platform', version: '1', chainId: CHAIN_ID },
+    types: MARKET_ACTION_TYPES,
+    primaryType: 'MarketAction',
+    message: {
+      actor,
+      table,
+      action,
+      content: keccak256(stringToBytes(payload)),
+      nonce,
+      deadline: BigInt(deadline),
+    },
+  });
+
+  return fetch(`${API_URL}${path}`, {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+      Authorization: `Bearer ${token}`,
+      'x-action-signature': signature,
+      'x-action-nonce': nonce,
+      'x-action-deadline': String(deadline),
+    },
+    body: payload,
+  });
+}
@@ -0,0 +1,22 @@
+use sea_orm::entity::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+// Leaves of the three tables as the latest keyed state proof committed them, by primary
+// key, with its receipt. The root is hex encoded, the leaves and receipt are kept as JSON.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Deserialize, Serialize)]
+#[sea_orm(table_name = "keyed_state")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub root: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub leaves: Json,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub receipt: Json,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
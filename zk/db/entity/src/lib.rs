@@ -4,6 +4,11 @@ pub mod tokenization;
 pub mod oil_token;
 pub mod comment;
 pub mod saved_token;
+pub mod state_block;
+pub mod proof_job;
+pub mod proof;
+pub mod synced_state;
+pub mod keyed_state;
 pub mod sea_orm_active_enums;
 
 // Re-export commonly used types
@@ -11,6 +16,11 @@ pub use oil_token::Entity as OilToken;
 pub use tokenization::Entity as Tokenization;
 pub use comment::Entity as Comment;
 pub use saved_token::Entity as SavedToken;
+pub use state_block::Entity as StateBlock;
+pub use proof_job::Entity as ProofJob;
+pub use proof::Entity as Proof;
+pub use synced_state::Entity as SyncedState;
+pub use keyed_state::Entity as KeyedState;
 pub use sea_orm_active_enums::{ Status, TokenStatus };
 
 // Re-export model types
@@ -18,3 +28,8 @@ pub use oil_token::Model as OilTokenModel;
 pub use tokenization::Model as TokenizationModel;
 pub use comment::Model as CommentModel;
 pub use saved_token::Model as SavedTokenModel;
+pub use state_block::Model as StateBlockModel;
+pub use proof_job::Model as ProofJobModel;
+pub use proof::Model as ProofModel;
+pub use synced_state::Model as SyncedStateModel;
+pub use keyed_state::Model as KeyedStateModel;
@@ -0,0 +1,30 @@
+use sea_orm::entity::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+// A receipt produced by a prover, kept with what it attests. The image id, journal digest
+// and fingerprint are hex encoded, the receipt and stats are kept as JSON.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Deserialize, Serialize)]
+#[sea_orm(table_name = "proof")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub guest: String,
+    pub image_id: String,
+    pub journal_digest: String,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub journal: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub receipt: Json,
+    pub receipt_kind: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub stats: Json,
+    // sha256 of the request the receipt proves
+    pub fingerprint: String,
+    pub created_at: DateTime,
+    pub verified_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,37 @@
+use sea_orm::entity::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+// A queued proof. The receipt and stats are set once the job is done, the error once it failed.
+// A running job is leased to `locked_by` until `lease_expires_at`. A job proves, then
+// compresses its receipt into `receipt_kind` when it is not of that kind yet.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Deserialize, Serialize)]
+#[sea_orm(table_name = "proof_job")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub kind: String,
+    pub status: String,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub input: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub receipt: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub stats: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub result: Option<Json>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub locked_by: Option<String>,
+    pub lease_expires_at: Option<DateTime>,
+    pub heartbeat_at: Option<DateTime>,
+    pub receipt_kind: String,
+    pub stage: String,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
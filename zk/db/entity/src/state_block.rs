@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+// One proven state of the chain. Roots are hex encoded, the receipt is kept as JSON.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Deserialize, Serialize)]
+#[sea_orm(table_name = "state_block")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub height: i64,
+    pub parent_root: String,
+    pub root: String,
+    pub kind: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub receipt: Json,
+    pub previous_updated_at: i64,
+    pub updated_at: i64,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
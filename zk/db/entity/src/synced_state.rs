@@ -0,0 +1,20 @@
+use sea_orm::entity::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+// Leaf hashes of the tables at a block proven by a sync, by primary key. The root is hex
+// encoded, the leaves are kept as JSON.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Deserialize, Serialize)]
+#[sea_orm(table_name = "synced_state")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub height: i64,
+    pub root: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub leaves: Json,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,12 +1,30 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20261018_000001_create_state_block_table;
+mod m20261018_000002_create_proof_job_table;
+mod m20261018_000003_add_proof_job_lease;
+mod m20261018_000004_create_proof_table;
+mod m20261018_000005_add_proof_job_stage;
+mod m20261018_000006_sync_id_sequences;
+mod m20261018_000007_create_synced_state_table;
+mod m20261018_000008_create_keyed_state_table;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20261018_000001_create_state_block_table::Migration),
+            Box::new(m20261018_000002_create_proof_job_table::Migration),
+            Box::new(m20261018_000003_add_proof_job_lease::Migration),
+            Box::new(m20261018_000004_create_proof_table::Migration),
+            Box::new(m20261018_000005_add_proof_job_stage::Migration),
+            Box::new(m20261018_000006_sync_id_sequences::Migration),
+            Box::new(m20261018_000007_create_synced_state_table::Migration),
+            Box::new(m20261018_000008_create_keyed_state_table::Migration)
+        ]
     }
 }
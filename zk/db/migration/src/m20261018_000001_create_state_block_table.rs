@@ -0,0 +1,46 @@
+use sea_orm_migration::{ prelude::*, schema::* };
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+pub enum StateBlock {
+    Table,
+    Height,
+    ParentRoot,
+    Root,
+    Kind,
+    Receipt,
+    PreviousUpdatedAt,
+    UpdatedAt,
+    CreatedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Create state_block table, one row per proven state keyed by its height
+        manager.create_table(
+            Table::create()
+                .table(StateBlock::Table)
+                .if_not_exists()
+                .col(big_integer(StateBlock::Height).primary_key())
+                .col(string(StateBlock::ParentRoot))
+                .col(string(StateBlock::Root))
+                .col(string(StateBlock::Kind))
+                .col(json_binary(StateBlock::Receipt))
+                .col(big_integer(StateBlock::PreviousUpdatedAt))
+                .col(big_integer(StateBlock::UpdatedAt))
+                .col(timestamp(StateBlock::CreatedAt))
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(StateBlock::Table).to_owned()).await?;
+
+        Ok(())
+    }
+}
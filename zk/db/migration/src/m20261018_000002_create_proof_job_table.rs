@@ -0,0 +1,60 @@
+use sea_orm_migration::{ prelude::*, schema::* };
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+pub enum ProofJob {
+    Table,
+    Id,
+    Kind,
+    Status,
+    Input,
+    Receipt,
+    Stats,
+    Result,
+    Error,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Create proof_job table, the queue of proofs run by the prover workers
+        manager.create_table(
+            Table::create()
+                .table(ProofJob::Table)
+                .if_not_exists()
+                .col(pk_auto(ProofJob::Id))
+                .col(string(ProofJob::Kind))
+                .col(string(ProofJob::Status))
+                .col(json_binary_null(ProofJob::Input))
+                .col(json_binary_null(ProofJob::Receipt))
+                .col(json_binary_null(ProofJob::Stats))
+                .col(json_binary_null(ProofJob::Result))
+                .col(text_null(ProofJob::Error))
+                .col(timestamp(ProofJob::CreatedAt))
+                .col(timestamp(ProofJob::UpdatedAt))
+                .to_owned()
+        ).await?;
+
+        // workers pick the oldest queued job
+        manager.create_index(
+            Index::create()
+                .name("idx_proof_job_status_id")
+                .table(ProofJob::Table)
+                .col(ProofJob::Status)
+                .col(ProofJob::Id)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(ProofJob::Table).to_owned()).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use sea_orm_migration::{ prelude::*, schema::* };
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+pub enum ProofJob {
+    Table,
+    Attempts,
+    LockedBy,
+    LeaseExpiresAt,
+    HeartbeatAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // A running job is leased to one worker, which renews the lease while it proves.
+        // A job whose lease runs out is picked up again by another worker.
+        manager.alter_table(
+            Table::alter()
+                .table(ProofJob::Table)
+                .add_column(integer(ProofJob::Attempts).default(0))
+                .add_column(string_null(ProofJob::LockedBy))
+                .add_column(timestamp_null(ProofJob::LeaseExpiresAt))
+                .add_column(timestamp_null(ProofJob::HeartbeatAt))
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(ProofJob::Table)
+                .drop_column(ProofJob::Attempts)
+                .drop_column(ProofJob::LockedBy)
+                .drop_column(ProofJob::LeaseExpiresAt)
+                .drop_column(ProofJob::HeartbeatAt)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::{ prelude::*, schema::* };
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+pub enum Proof {
+    Table,
+    Id,
+    Guest,
+    ImageId,
+    JournalDigest,
+    Journal,
+    Receipt,
+    ReceiptKind,
+    Stats,
+    Fingerprint,
+    CreatedAt,
+    VerifiedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Create proof table, every receipt the provers produced
+        manager.create_table(
+            Table::create()
+                .table(Proof::Table)
+                .if_not_exists()
+                .col(pk_auto(Proof::Id))
+                .col(string(Proof::Guest))
+                .col(string(Proof::ImageId))
+                .col(string(Proof::JournalDigest))
+                .col(json_binary_null(Proof::Journal))
+                .col(json_binary(Proof::Receipt))
+                .col(string(Proof::ReceiptKind))
+                .col(json_binary(Proof::Stats))
+                .col(string(Proof::Fingerprint))
+                .col(timestamp(Proof::CreatedAt))
+                .col(timestamp_null(Proof::VerifiedAt))
+                .to_owned()
+        ).await?;
+
+        // proofs are looked up by the request that produced them
+        manager.create_index(
+            Index::create()
+                .name("idx_proof_fingerprint")
+                .table(Proof::Table)
+                .col(Proof::Fingerprint)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Proof::Table).to_owned()).await?;
+
+        Ok(())
+    }
+}
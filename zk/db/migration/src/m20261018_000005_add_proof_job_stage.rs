@@ -0,0 +1,40 @@
+use sea_orm_migration::{ prelude::*, schema::* };
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+pub enum ProofJob {
+    Table,
+    ReceiptKind,
+    Stage,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // A job proves, then compresses its receipt into the kind it was asked for as a
+        // stage of its own, which any worker can pick up.
+        manager.alter_table(
+            Table::alter()
+                .table(ProofJob::Table)
+                .add_column(string(ProofJob::ReceiptKind).default("composite"))
+                .add_column(string(ProofJob::Stage).default("prove"))
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(ProofJob::Table)
+                .drop_column(ProofJob::ReceiptKind)
+                .drop_column(ProofJob::Stage)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+}
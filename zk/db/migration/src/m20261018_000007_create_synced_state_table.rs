@@ -0,0 +1,39 @@
+use sea_orm_migration::{ prelude::*, schema::* };
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+pub enum SyncedState {
+    Table,
+    Height,
+    Root,
+    Leaves,
+    CreatedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Leaf hashes of every block proven by a sync, keyed by its height. A delta sync
+        // proves only the rows changed since, whichever process runs it.
+        manager.create_table(
+            Table::create()
+                .table(SyncedState::Table)
+                .if_not_exists()
+                .col(big_integer(SyncedState::Height).primary_key())
+                .col(string(SyncedState::Root))
+                .col(json_binary(SyncedState::Leaves))
+                .col(timestamp(SyncedState::CreatedAt))
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(SyncedState::Table).to_owned()).await?;
+
+        Ok(())
+    }
+}
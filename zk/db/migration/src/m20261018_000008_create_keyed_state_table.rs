@@ -0,0 +1,41 @@
+use sea_orm_migration::{ prelude::*, schema::* };
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+pub enum KeyedState {
+    Table,
+    Id,
+    Root,
+    Leaves,
+    Receipt,
+    CreatedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Leaves and receipt of the latest keyed state proof. Keyed proofs are built from
+        // them, so they check against that receipt whatever was written since.
+        manager.create_table(
+            Table::create()
+                .table(KeyedState::Table)
+                .if_not_exists()
+                .col(pk_auto(KeyedState::Id))
+                .col(string(KeyedState::Root))
+                .col(json_binary(KeyedState::Leaves))
+                .col(json_binary(KeyedState::Receipt))
+                .col(timestamp(KeyedState::CreatedAt))
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(KeyedState::Table).to_owned()).await?;
+
+        Ok(())
+    }
+}
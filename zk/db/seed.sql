@@ -178,4 +178,10 @@ INSERT INTO saved_token (
     (2, '0x2345678901bcdef2345678901bcdef2345678901', 2, '2024-01-02T16:00:00Z'),
     (3, '0x3456789012cdef3456789012cdef3456789012c', 3, '2024-01-03T17:00:00Z'),
     (4, '0x4567890123def4567890123def4567890123def', 4, '2024-01-04T18:00:00Z'),
-    (5, '0x5678901234ef5678901234ef5678901234ef567', 5, '2024-01-05T19:00:00Z');
\ No newline at end of file
+    (5, '0x5678901234ef5678901234ef5678901234ef567', 5, '2024-01-05T19:00:00Z');
+
+-- Move the id sequences past the seeded ids, new rows take their id from them
+SELECT setval(pg_get_serial_sequence('oil_token', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM oil_token;
+SELECT setval(pg_get_serial_sequence('tokenization', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM tokenization;
+SELECT setval(pg_get_serial_sequence('comment', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM comment;
+SELECT setval(pg_get_serial_sequence('saved_token', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM saved_token;
@@ -52,10 +52,25 @@ pub async fn seed_database(db: &DatabaseConnection) -> Result<(), DbErr> {
     seed_saved_tokens(db).await?;
     println!("✅ Seeded saved tokens");
 
+    // Rows are seeded with their ids, move the id sequences past them
+    sync_id_sequences(db).await?;
+
     println!("🎉 Oil tokenization database seeding completed successfully!");
     Ok(())
 }
 
+async fn sync_id_sequences(db: &DatabaseConnection) -> Result<(), DbErr> {
+    for table in ["oil_token", "tokenization", "comment", "saved_token"] {
+        db.execute_unprepared(
+            &format!(
+                "SELECT setval(pg_get_serial_sequence('{0}', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM {0}",
+                table
+            )
+        ).await?;
+    }
+    Ok(())
+}
+
 async fn clear_database(db: &DatabaseConnection) -> Result<(), DbErr> {
     // Clear in reverse order of dependencies
     saved_token::Entity::delete_many().exec(db).await?;
@@ -112,7 +127,6 @@ pub async fn seed_oil_tokens_from_json(db: &DatabaseConnection, path: &str) -> R
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         };
         model.insert(db).await?;
     }
@@ -138,7 +152,6 @@ async fn seed_tokenizations(db: &DatabaseConnection) -> Result<Vec<tokenization:
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         tokenization::ActiveModel {
             id: Set(2),
@@ -157,7 +170,6 @@ async fn seed_tokenizations(db: &DatabaseConnection) -> Result<Vec<tokenization:
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         tokenization::ActiveModel {
             id: Set(3),
@@ -176,7 +188,6 @@ async fn seed_tokenizations(db: &DatabaseConnection) -> Result<Vec<tokenization:
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         tokenization::ActiveModel {
             id: Set(4),
@@ -195,7 +206,6 @@ async fn seed_tokenizations(db: &DatabaseConnection) -> Result<Vec<tokenization:
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         tokenization::ActiveModel {
             id: Set(5),
@@ -214,7 +224,6 @@ async fn seed_tokenizations(db: &DatabaseConnection) -> Result<Vec<tokenization:
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         }
     ];
 
@@ -248,7 +257,6 @@ async fn seed_comments(db: &DatabaseConnection) -> Result<Vec<comment::Model>, D
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         comment::ActiveModel {
             id: Set(2),
@@ -267,7 +275,6 @@ async fn seed_comments(db: &DatabaseConnection) -> Result<Vec<comment::Model>, D
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         comment::ActiveModel {
             id: Set(3),
@@ -286,7 +293,6 @@ async fn seed_comments(db: &DatabaseConnection) -> Result<Vec<comment::Model>, D
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         comment::ActiveModel {
             id: Set(4),
@@ -305,7 +311,6 @@ async fn seed_comments(db: &DatabaseConnection) -> Result<Vec<comment::Model>, D
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         comment::ActiveModel {
             id: Set(5),
@@ -326,7 +331,6 @@ async fn seed_comments(db: &DatabaseConnection) -> Result<Vec<comment::Model>, D
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         comment::ActiveModel {
             id: Set(6),
@@ -347,7 +351,6 @@ async fn seed_comments(db: &DatabaseConnection) -> Result<Vec<comment::Model>, D
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         comment::ActiveModel {
             id: Set(7),
@@ -366,7 +369,6 @@ async fn seed_comments(db: &DatabaseConnection) -> Result<Vec<comment::Model>, D
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         }
     ];
 
@@ -391,7 +393,6 @@ async fn seed_saved_tokens(db: &DatabaseConnection) -> Result<Vec<saved_token::M
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         saved_token::ActiveModel {
             id: Set(2),
@@ -403,7 +404,6 @@ async fn seed_saved_tokens(db: &DatabaseConnection) -> Result<Vec<saved_token::M
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         saved_token::ActiveModel {
             id: Set(3),
@@ -415,7 +415,6 @@ async fn seed_saved_tokens(db: &DatabaseConnection) -> Result<Vec<saved_token::M
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         saved_token::ActiveModel {
             id: Set(4),
@@ -427,7 +426,6 @@ async fn seed_saved_tokens(db: &DatabaseConnection) -> Result<Vec<saved_token::M
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         saved_token::ActiveModel {
             id: Set(5),
@@ -439,7 +437,6 @@ async fn seed_saved_tokens(db: &DatabaseConnection) -> Result<Vec<saved_token::M
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         saved_token::ActiveModel {
             id: Set(6),
@@ -451,7 +448,6 @@ async fn seed_saved_tokens(db: &DatabaseConnection) -> Result<Vec<saved_token::M
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         saved_token::ActiveModel {
             id: Set(7),
@@ -463,7 +459,6 @@ async fn seed_saved_tokens(db: &DatabaseConnection) -> Result<Vec<saved_token::M
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         },
         saved_token::ActiveModel {
             id: Set(8),
@@ -475,7 +470,6 @@ async fn seed_saved_tokens(db: &DatabaseConnection) -> Result<Vec<saved_token::M
                     .with_timezone(&Utc)
                     .naive_utc()
             ),
-            ..Default::default()
         }
     ];
 
@@ -1,23 +1,31 @@
-#[warn(unused_assignments)]
 use axum::{
     extract::{ Request },
-    http::{ header, HeaderMap, HeaderValue, StatusCode },
+    http::{ header, HeaderMap, HeaderValue },
     middleware::Next,
     response::{ IntoResponse, Response },
 };
-use methods::{ VERIFY_ELF, VERIFY_ID };
-use risc0_zkvm::{ default_prover, ExecutorEnv, Receipt };
+use methods::VERIFY_ID;
+use oil_tokenization_core::{ SignatureScheme, VerifyCommit as VerifyState, VerifyParams };
+use risc0_zkvm::Receipt;
 use sea_orm::sqlx::types::uuid;
 use serde::{ Deserialize, Serialize };
-use ethers::{ types::Address, utils::hex::{ hex } };
+use ethers::{ types::Address, utils::hex };
+use ethers::utils::to_checksum;
 use axum::{ Json };
 use serde_json::{ json, Value };
 use jsonwebtoken::{ decode, DecodingKey, Validation };
-use k256::{ ecdsa::{ RecoveryId, Signature, VerifyingKey } };
-// use ecdsa::SigningKey;
-use sha3::{ Digest, Keccak256 };
-
-use crate::{ jwt::{ issue_token, Claims }, redis::{ get_nonce, store_nonce }, SessionStats };
+use std::sync::Arc;
+
+use crate::{
+    eip712::login_typed_data,
+    error::ApiError,
+    job::{ enqueue_job, enqueue_response, JobKind },
+    jwt::{ issue_token, Claims },
+    prover::{ verify_receipt, Guest, ProveRequest, ProverBackend, ReceiptKind },
+    redis::{ consume_nonce, get_nonce, store_nonce },
+    siwe::{ SiweConfig, LOGIN_PROOF_TTL_SECONDS, LOGIN_TTL_SECONDS },
+    SessionStats,
+};
 
 #[derive(Deserialize)]
 pub struct SignaturePayload {
@@ -26,14 +34,6 @@ pub struct SignaturePayload {
     pub signature: String, // 65-byte signature (hex)
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct VerifyParams {
-    pub message: String,
-    pub signature_bytes: String,
-    pub expected_addr: String,
-    pub timestamp: i64,
-    pub username: String,
-}
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct VerifyPayload {
     message: String,
@@ -41,265 +41,222 @@ pub struct VerifyPayload {
     expected_addr: String,
     username: String,
     nonce: String,
+    // personal_sign (default) or typed-data signature of the login message
+    #[serde(default)]
+    scheme: SignatureScheme,
+    // native login only: also prove the login in the background
+    #[serde(default)]
+    attest: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct VerifyCommit {
-    receipt: Receipt,
-    stats: SessionStats,
+    pub receipt: Receipt,
+    pub stats: SessionStats,
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct VerifyState {
-    pub verified: bool,
-    pub address: String,
-    pub timestamp: i64,
-    pub username: String,
-}
 
 impl VerifyCommit {
-    //  pub fn get_state() {}
     pub fn get_commit(&self) -> Result<VerifyState, String> {
         let state = self.receipt.journal.decode().map_err(|e| e.to_string())?;
         Ok(state)
     }
     pub fn verify_and_get_commit(&self) -> Result<VerifyState, String> {
-        self.receipt.verify(VERIFY_ID).map_err(|e| e.to_string())?;
+        verify_receipt(&self.receipt, VERIFY_ID)?;
         self.get_commit()
     }
 }
 pub async fn verify_signature(Json(payload): Json<SignaturePayload>) -> Result<
     Json<String>,
-    (StatusCode, String)
+    ApiError
 > {
     let sig_bytes = match
         hex::decode(payload.signature.strip_prefix("0x").unwrap_or(&payload.signature))
     {
         Ok(b) => b,
         Err(_) => {
-            return Err((StatusCode::BAD_REQUEST, "Invalid signature hex".into()));
+            return Err(ApiError::Validation("Invalid signature hex".into()));
         }
     };
 
     let signature = match ethers::types::Signature::try_from(&sig_bytes[..]) {
         Ok(sig) => sig,
         Err(_) => {
-            return Err((StatusCode::BAD_REQUEST, "Invalid signature format".into()));
+            return Err(ApiError::Validation("Invalid signature format".into()));
         }
     };
 
-    // Hash the message with Ethereum's prefixing
-    // let message_hash = hash_message(&payload.message);
-
     // Recover address from signature
     let recovered = match signature.recover(payload.message) {
         Ok(addr) => addr,
         Err(_) => {
-            return Err((StatusCode::UNAUTHORIZED, "Signature verification failed".into()));
+            return Err(ApiError::Unauthorized("Signature verification failed".into()));
         }
     };
 
     // Compare addresses (case-insensitive)
     let claimed = payload.address
         .parse::<Address>()
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid address".into()))?;
+        .map_err(|_| ApiError::Validation("Invalid address".into()))?;
 
     if claimed != recovered {
-        return Err((StatusCode::UNAUTHORIZED, "Address mismatch".into()));
+        return Err(ApiError::Unauthorized("Address mismatch".into()));
     }
 
     // ✅ Verified! You can now issue JWT or create zk proof.
     Ok(Json(format!("Signature verified for address: {:?}", claimed)))
 }
 
-// pub struct NoncePayload {
-//     address: String,
-// }
-pub async fn get_verify_handler(
-    // axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>,
-    // Json(payload): Json<NoncePayload>
-) -> Result<Json<Value>, (StatusCode, String)> {
-    // convert to Vec
-    let nonce = uuid::Uuid::new_v4().to_string();
-    // let now = chrono::Utc::now().timestamp();
-    let rs = store_nonce(&nonce).await.unwrap();
+#[derive(Deserialize)]
+pub struct NonceQuery {
+    address: String,
+}
+
+// Handler to get a SIWE login message for `address`, to be signed by its wallet
+pub async fn get_verify_handler(axum::extract::Query(query): axum::extract::Query<NonceQuery>) -> Result<
+    Json<Value>,
+    ApiError
+> {
+    let address = query.address
+        .parse::<Address>()
+        .map_err(|_| ApiError::Validation("Invalid address".to_string()))?;
+    // SIWE nonces are alphanumeric
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    let siwe = SiweConfig::from_env();
+    let message = siwe.login_message(&to_checksum(&address, None), &nonce);
+    let rs = store_nonce(&nonce, &message.to_message()).await?;
+    // the same login as typed data, for wallets signing with eth_signTypedData_v4
     Ok(Json(json!({
       "nonce": rs.0,
-      "msg": rs.1
+      "msg": rs.1,
+      "typed_data": login_typed_data(&message, siwe.chain_id)
     })))
 }
-fn _keccak256(data: &[u8]) -> [u8; 32] {
-    let mut hasher = Keccak256::new();
-    hasher.update(data);
-    let result = hasher.finalize();
-    let mut output = [0u8; 32];
-    output.copy_from_slice(&result);
-    output
-}
-// fn recover_ethereum_address(message: &[u8], signature_bytes: [u8; 65]) -> Option<[u8; 20]> {
-//     // let msg_hash = ethereum_prefixed_message(message);
-//     let message_hash = hash_message(message);
-//     // let sig = Signature::from_bytes(&signature_bytes[..64]).ok()?;
-//     let sig = Signature::from_bytes(GenericArray::from_slice(&signature_bytes[..64])).ok()?;
-
-//     let recovery_id = Id::new(signature_bytes[64] % 27, false).ok()?; // recovery_id should be 0 or 1
-//     let recoverable_sig = Signature::new(&sig, recovery_id).ok()?;
-
-//     // Recover public key
-//     let pubkey = recoverable_sig.recover_verify_key_from_digest_bytes(message_hash.into()).ok()?;
-
-//     // Compute Ethereum address
-//     let pubkey_encoded = pubkey.to_encoded_point(false);
-//     let pubkey_bytes = pubkey_encoded.as_bytes();
-//     let hash = keccak256(&pubkey_bytes[1..]); // Remove 0x04 prefix
-//     let mut addr = [0u8; 20];
-//     addr.copy_from_slice(&hash[12..]);
-//     Some(addr)
-// }
-
-fn _recover_ethereum_address(signature_hex: &str, message: &str) -> Result<[u8; 20], String> {
-    let signature_bytes = hex
-        ::decode(signature_hex.strip_prefix("0x").unwrap_or(signature_hex))
-        .map_err(|e| format!("Invalid signature hex: {}", e))?;
-
-    if signature_bytes.len() != 65 {
-        return Err(format!("Expected 65-byte signature, got {}", signature_bytes.len()));
+// Guest input for the login `message` stored under the payload nonce.
+fn verify_params(payload: &VerifyPayload, message: String) -> VerifyParams {
+    let siwe = SiweConfig::from_env();
+    VerifyParams {
+        message,
+        signature_bytes: payload.signature_bytes.clone(),
+        scheme: payload.scheme,
+        expected_addr: payload.expected_addr.clone(),
+        domain: siwe.domain,
+        chain_id: siwe.chain_id,
+        timestamp: chrono::Utc::now().timestamp(),
+        username: payload.username.clone(),
     }
+}
 
-    let signature = Signature::try_from(&signature_bytes[..64]).map_err(|e|
-        format!("Invalid signature: {}", e)
-    )?;
-
-    let v = signature_bytes[64];
-    let recovery_id_val = if v >= 27 { v - 27 } else { v % 2 };
-    let recid = RecoveryId::try_from(recovery_id_val).map_err(|e|
-        format!("Invalid recovery ID: {}", e)
-    )?;
-
-    // Create Ethereum message hash with proper prefix
-    let message_bytes = message.as_bytes();
-    let prefix = format!("\x19Ethereum Signed Message:\n{}", message_bytes.len());
-    let mut hasher = Keccak256::new();
-    hasher.update(prefix.as_bytes());
-    hasher.update(message_bytes);
-    let message_hash = hasher.finalize();
-    // let msg_hh = Keccak256::digest(message_hash);
-
-    let recovered_key = VerifyingKey::recover_from_prehash(
-        &message_hash,
-        &signature,
-        recid
-    ).map_err(|e| format!("Failed to recover public key: {}", e))?;
-
-    let encoded_point = recovered_key.to_encoded_point(false);
-    let public_key_bytes = encoded_point.as_bytes();
-
-    let hash = Keccak256::digest(&public_key_bytes[1..]);
-
-    let mut address = [0u8; 20];
-    address.copy_from_slice(&hash[12..]);
-
-    Ok(address)
+// Proves a login with the verify guest. Blocking, takes seconds to minutes.
+pub fn prove_verify(prover: &dyn ProverBackend, params: &VerifyParams) -> Result<VerifyCommit, String> {
+    let proof = prover.prove(&ProveRequest::new(Guest::Verify, params)?)?;
+    tracing::info!(cycles = proof.stats.total_cycles, "Proved login");
+    Ok(VerifyCommit {
+        receipt: proof.receipt,
+        stats: proof.stats,
+    })
 }
+
+// Handler to queue the zk proof of a login. The job receipt is exchanged for a token at
+// /api/auth/verify, see /api/jobs/{id}
 pub async fn verify_signature_handler(
-    // axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>,
     Json(payload): Json<VerifyPayload>
-) -> Result<Json<Value>, (StatusCode, String)> {
-    // convert to Vec
-    // let message = get_nonce(&payload.nonce).await;
-    // let message = payload.message.clone();
-    let message = get_nonce(&payload.nonce).await;
+) -> Result<Json<Value>, ApiError> {
+    let message = get_nonce(&payload.nonce).await?;
     let message = message.ok_or_else(|| {
-        (StatusCode::BAD_REQUEST, "Failed to verify nonce".to_string())
+        ApiError::Validation("Failed to verify nonce".to_string())
     })?;
-    // let message = message.ok_or_else(|| {
-    //     (StatusCode::BAD_REQUEST, "Failed to verify nonce".to_string())
-    // })?;
-    eprintln!("Get nonce {}", message);
-    // let message_bytes: [u8] = hex::decode(&message).expect("Invalid mesg");
-    // let signature_bytes: [u8; 65] = hex
-    //     ::decode(&payload.signature_bytes)
-    //     .expect("Invalid hex")
-    //     .try_into()
-    //     .expect("expected 65 bytes");
-    // let expected_addr: [u8; 20] = hex
-    //     ::decode(&payload.expected_addr)
-    //     .expect("Invalid hex")
-    //     .try_into()
-    //     .expect("expected 20 bytes");
+    let vec_payload = verify_params(&payload, message);
+    enqueue_response(&db, JobKind::Verify, Some(json!(vec_payload)), ReceiptKind::default()).await
+}
 
+// Handler to exchange the receipt of a proven login for a token
+pub async fn verify_auth_handler(Json(payload): Json<VerifyCommit>) -> Result<Response, ApiError> {
+    let commit = payload.verify_and_get_commit().map_err(ApiError::Unauthorized)?;
+    // a login whose signature did not verify must not touch its nonce, or anyone could
+    // burn the nonce of someone else's pending login
+    if !commit.verified {
+        return Err(ApiError::Unauthorized("User not verified".to_string()));
+    }
+    // the guest checked the message at the time the login was submitted, which must be
+    // recent enough, and the message must be for this domain and chain
+    let siwe = SiweConfig::from_env();
     let now = chrono::Utc::now().timestamp();
-    let vec_payload = VerifyParams {
-        message: message.clone(),
-        signature_bytes: payload.signature_bytes.clone(),
-        expected_addr: payload.expected_addr.clone(),
-        timestamp: now,
-        username: payload.username,
+    let submitted = commit.timestamp;
+    let Some(message) = commit.message.as_ref().filter(|message| {
+        message.check(&siwe.domain, siwe.chain_id, submitted).is_ok() &&
+            message.issued_at >= submitted - LOGIN_TTL_SECONDS &&
+            submitted <= now &&
+            submitted >= now - LOGIN_PROOF_TTL_SECONDS
+    }) else {
+        return Err(ApiError::Unauthorized("Login message is expired or not for this domain".to_string()));
     };
-    //  let recovered_addr = recover_ethereum_address(&payload.signature_bytes, &message).map_err(|e| {
-    //      (StatusCode::BAD_REQUEST, e)
-    //  })?;
-    //  let eth_address = format!("0x{}", hex::encode(recovered_addr));
-    //  println!("{}", eth_address);
-
-    //  eprintln!("Recovered address{:?}", eth_address);
+    // each login message is good for one session: its nonce is consumed on first use,
+    // so replaying the same receipt finds nothing left to consume
+    let issued = consume_nonce(&message.nonce).await?;
+    if issued.as_deref() != Some(message.to_message().as_str()) {
+        return Err(ApiError::Unauthorized("Login message was already used".to_string()));
+    }
+    let key = issue_token(&commit.address, &commit.username)?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        axum::http::header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", key)).map_err(|_|
+            ApiError::Internal("Invalid header value".to_string())
+        )?
+    );
 
-    let env = ExecutorEnv::builder().write(&vec_payload).unwrap().build().unwrap();
-    let prover = default_prover();
-    let prove_info = prover.prove(env, VERIFY_ELF).unwrap();
-    eprint!("Prove info {:?}", prove_info.stats);
-    let verify_commit = VerifyCommit {
-        receipt: prove_info.receipt,
-        stats: SessionStats {
-            segments: prove_info.stats.segments,
-            total_cycles: prove_info.stats.total_cycles,
-            user_cycles: prove_info.stats.user_cycles,
-            paging_cycles: prove_info.stats.paging_cycles,
-            reserved_cycles: prove_info.stats.reserved_cycles,
-        },
-    };
-    // eprint!("{:?}", verify_commit);
-    Ok(Json(json!(verify_commit)))
+    Ok((headers, Json(json!(commit))).into_response())
 }
 
-pub async fn verify_auth_handler(
-    // axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>,
-    Json(payload): Json<VerifyCommit>
-) -> Result<Response, StatusCode> {
-    let state = payload;
-    let commit = state
-        .verify_and_get_commit()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
-        .unwrap();
-    // .ok()
-    // .unwrap();
-    // let addr = std::str::from_utf8(&commit.address).unwrap();
-    let mut key: Option<String> = None;
-    eprintln!("Verified {:?}", commit);
-    if commit.verified {
-        key = Some(issue_token(&commit.address, &commit.username));
+// Handler to log in without waiting for a proof. The signature is checked natively with
+// the same checks as the verify guest and the token is issued right away. With `attest`
+// the zk proof of the login is queued as a verify job, see /api/jobs/{id}.
+pub async fn native_login_handler(
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>,
+    Json(payload): Json<VerifyPayload>
+) -> Result<Response, ApiError> {
+    let message = get_nonce(&payload.nonce).await?.ok_or_else(|| {
+        ApiError::Validation("Failed to verify nonce".to_string())
+    })?;
+    let params = verify_params(&payload, message.clone());
+    let commit = params.clone().verify();
+    if !commit.verified {
+        return Err(ApiError::Unauthorized("Signature verification failed".to_string()));
+    }
+    // queued before the nonce is consumed, so a failure leaves the login to be retried
+    let attestation = if payload.attest {
+        Some(enqueue_job(&db, JobKind::Verify, Some(json!(params)), ReceiptKind::default()).await?)
     } else {
-        return Ok(
-            (
-                Json(
-                    json!({
-          "message": "User not verified",
-          "status": "failed"
-        })
-                ),
-            ).into_response()
-        );
+        None
+    };
+    // one session per login message, as for the zk path
+    let issued = consume_nonce(&payload.nonce).await?;
+    if issued.as_deref() != Some(message.as_str()) {
+        return Err(ApiError::Unauthorized("Login message was already used".to_string()));
     }
+
+    let token = issue_token(&commit.address, &commit.username)?;
     let mut headers = HeaderMap::new();
     headers.insert(
         axum::http::header::AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", key.unwrap()))
-            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Invalid header value".to_string()))
-            .unwrap()
+        HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_|
+            ApiError::Internal("Invalid header value".to_string())
+        )?
     );
-
-    Ok((headers, Json(json!(commit))).into_response())
-    // Ok(Json(json!(commit)))
+    Ok(
+        (
+            headers,
+            Json(
+                json!({
+        "status": "success",
+        "data": commit,
+        "attestation_job_id": attestation
+    })
+            ),
+        ).into_response()
+    )
 }
 use tokio::{ task_local };
 
@@ -307,25 +264,33 @@ task_local! {
     pub static USER: Claims;
 }
 
-pub async fn auth(req: Request, next: Next) -> Result<Response, StatusCode> {
+// Claims of the logged in caller, with the address parsed. Only set behind the auth
+// middleware, anywhere else the caller is unauthorized.
+pub fn current_user() -> Result<Claims, ApiError> {
+    let user = USER.try_with(Claims::clone).map_err(|_|
+        ApiError::Unauthorized("Not logged in".to_string())
+    )?;
+    let addr = user.addr
+        .parse::<Address>()
+        .map_err(|_| ApiError::Validation("Invalid address".to_string()))?;
+    Ok(Claims { addr: addr.to_string(), ..user })
+}
+
+pub async fn auth(req: Request, next: Next) -> Result<Response, ApiError> {
     let auth_header = req
         .headers()
         .get(header::AUTHORIZATION)
         .and_then(|header| header.to_str().ok())
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-    let token = auth_header.strip_prefix("Bearer ").ok_or(StatusCode::UNAUTHORIZED)?;
+        .ok_or_else(|| ApiError::Unauthorized("Missing authorization header".to_string()))?;
+    let token = auth_header
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| ApiError::Unauthorized("Expected a Bearer token".to_string()))?;
 
     if let Some(current_user) = authorize_current_user(token).await {
         Ok(USER.scope(current_user, next.run(req)).await)
     } else {
-        Err(StatusCode::UNAUTHORIZED)
+        Err(ApiError::Unauthorized("Invalid or expired token".to_string()))
     }
-
-    //     // State is setup here in the middleware
-    //     Ok(USER.scope(current_user, next.run(req)).await)
-    // } else {
-    //     Err(StatusCode::UNAUTHORIZED)
-    // }
 }
 async fn authorize_current_user(auth_token: &str) -> Option<Claims> {
     let token_data = decode::<Claims>(
@@ -337,18 +302,3 @@ async fn authorize_current_user(auth_token: &str) -> Option<Claims> {
     // check expiry
     Some(token_data.claims)
 }
-
-struct UserResponse;
-
-impl IntoResponse for UserResponse {
-    fn into_response(self) -> Response {
-        // State is accessed here in the IntoResponse implementation
-        let current_user = USER.with(|u| Claims {
-            username: u.username.clone(),
-            addr: u.addr.clone(),
-            exp: u.exp.clone(),
-        });
-
-        (StatusCode::OK, Json(json!(current_user))).into_response()
-    }
-}
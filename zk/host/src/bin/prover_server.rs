@@ -0,0 +1,114 @@
+use std::env;
+use std::sync::Arc;
+
+use axum::{
+    extract::{ DefaultBodyLimit, Request, State },
+    http::header,
+    middleware::{ self, Next },
+    response::Response,
+    routing::post,
+    Json,
+    Router,
+};
+use dotenv::dotenv;
+use host::error::ApiError;
+use host::prover::{
+    dev_receipts_accepted,
+    prover_token,
+    CompressRequest,
+    DevModeProver,
+    LocalProver,
+    Proof,
+    ProveRequest,
+    SharedProver,
+};
+
+// Largest request body unless PROVER_MAX_BODY_BYTES says otherwise. Requests carry the
+// receipts of composed proofs, a few megabytes at most.
+const DEFAULT_MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+// Prover server for PROVER=remote. It proves requests with the local prover, or with fake
+// dev-mode receipts when RISC0_DEV_MODE and ACCEPT_DEV_RECEIPTS are set, so it can also
+// stand in for a remote prover during development. Every request needs the shared
+// PROVER_TOKEN as a Bearer token.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+    let dev_mode = env::var("RISC0_DEV_MODE").is_ok_and(|v| v == "1" || v == "true");
+    if dev_mode && !dev_receipts_accepted() {
+        return Err("Dev mode makes fake receipts, set ACCEPT_DEV_RECEIPTS=1 to accept them".into());
+    }
+    let token: Arc<str> = prover_token()?.into();
+    let max_body_bytes = env
+        ::var("PROVER_MAX_BODY_BYTES")
+        .ok()
+        .and_then(|bytes| bytes.parse().ok())
+        .unwrap_or(DEFAULT_MAX_BODY_BYTES);
+    let prover: SharedProver = if dev_mode {
+        Arc::new(DevModeProver)
+    } else {
+        Arc::new(LocalProver)
+    };
+
+    let app = Router::new()
+        .route("/prove", post(prove_handler))
+        .route("/compress", post(compress_handler))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(middleware::from_fn_with_state(token, require_token))
+        .with_state(prover);
+
+    let addr = env::var("PROVER_SERVER_ADDR").unwrap_or_else(|_| "0.0.0.0:3002".to_string());
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    println!("Prover server running on http://{}", addr);
+    axum::serve(listener, app.into_make_service()).await?;
+    Ok(())
+}
+
+// Refuses requests without the shared token, before their body is read.
+async fn require_token(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next
+) -> Result<Response, ApiError> {
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::Unauthorized("Missing prover token".to_string()))?;
+    if !same_token(given.as_bytes(), token.as_bytes()) {
+        return Err(ApiError::Unauthorized("Invalid prover token".to_string()));
+    }
+    Ok(next.run(request).await)
+}
+
+// Compares in time independent of where the tokens differ.
+fn same_token(given: &[u8], token: &[u8]) -> bool {
+    given.len() == token.len() &&
+        given
+            .iter()
+            .zip(token)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn prove_handler(
+    State(prover): State<SharedProver>,
+    Json(request): Json<ProveRequest>
+) -> Result<Json<Proof>, ApiError> {
+    let proof = tokio::task
+        ::spawn_blocking(move || prover.prove(&request))
+        .await?
+        .map_err(ApiError::Prover)?;
+    Ok(Json(proof))
+}
+
+async fn compress_handler(
+    State(prover): State<SharedProver>,
+    Json(request): Json<CompressRequest>
+) -> Result<Json<Proof>, ApiError> {
+    let proof = tokio::task
+        ::spawn_blocking(move || prover.compress(request))
+        .await?
+        .map_err(ApiError::Prover)?;
+    Ok(Json(proof))
+}
@@ -0,0 +1,36 @@
+use std::env;
+use std::process;
+use std::sync::Arc;
+
+use db::establish_connection;
+use dotenv::dotenv;
+use host::job::{ spawn_workers, worker_count };
+use host::proof::record_proofs;
+use host::prover::prover_from_env;
+
+// Standalone prover. It proves the jobs the API servers queue in proof_job, so proving
+// scales apart from the API: run as many as needed, next to API servers started with
+// PROVER_WORKERS=0.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| {
+        eprintln!("Error: DATABASE_URL environment variable is required");
+        process::exit(1);
+    });
+    let db = Arc::new(establish_connection(&database_url).await?);
+
+    tracing_subscriber
+        ::fmt()
+        .with_env_filter(tracing_subscriber::filter::EnvFilter::from_default_env())
+        .init();
+
+    let workers = worker_count().max(1);
+    let prover = record_proofs(db.clone(), prover_from_env()?);
+    spawn_workers(db, prover, workers);
+    println!("Prover worker running {} workers", workers);
+
+    // jobs in progress at shutdown are picked up again once their lease runs out
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
@@ -0,0 +1,266 @@
+use axum::Json;
+use chrono::Utc;
+use entity::{ proof_job, state_block };
+use oil_tokenization_core::{ verify_blocks, BlockHeader, BlockImage, TableRoots };
+use methods::{ INIT_OVERALL_ID, INIT_STATE_ID, SYNC_DELTA_ID, TRANSITION_ID };
+use risc0_zkvm::{ sha::Digest, Receipt };
+use sea_orm::{
+    ActiveModelTrait,
+    ColumnTrait,
+    ConnectionTrait,
+    DatabaseConnection,
+    EntityTrait,
+    QueryFilter,
+    QueryOrder,
+    QuerySelect,
+};
+use serde::Deserialize;
+use serde_json::{ Value, json };
+use std::sync::Arc;
+
+use crate::{
+    error::ApiError,
+    job::{ enqueue_job, JobKind, JobStatus },
+    prover::{ verify_receipt, ReceiptKind },
+};
+
+// Guest that proved a block, which decides how its receipt is verified and decoded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockKind {
+    Overall,
+    Transition,
+    Delta,
+}
+
+impl BlockKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockKind::Overall => "overall",
+            BlockKind::Transition => "transition",
+            BlockKind::Delta => "delta",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Result<Self, String> {
+        match kind {
+            "overall" => Ok(BlockKind::Overall),
+            "transition" => Ok(BlockKind::Transition),
+            "delta" => Ok(BlockKind::Delta),
+            _ => Err(format!("Unknown block kind {}", kind)),
+        }
+    }
+
+    // Image the guest of this kind proves blocks with.
+    pub fn image(&self) -> BlockImage {
+        match self {
+            BlockKind::Overall =>
+                BlockImage::Overall {
+                    image_id: Digest::from(INIT_OVERALL_ID),
+                    state_image_id: Digest::from(INIT_STATE_ID),
+                },
+            BlockKind::Transition => BlockImage::Transition(Digest::from(TRANSITION_ID)),
+            BlockKind::Delta => BlockImage::Delta(Digest::from(SYNC_DELTA_ID)),
+        }
+    }
+
+    // Verifies `receipt` against the image of this kind and returns the header it commits.
+    pub fn verify(&self, receipt: &Receipt) -> Result<BlockHeader, String> {
+        Ok(self.verify_roots(receipt)?.0)
+    }
+
+    // Verifies `receipt` against the image of this kind and returns the header it commits
+    // with the roots of the three tables.
+    pub fn verify_roots(&self, receipt: &Receipt) -> Result<(BlockHeader, TableRoots), String> {
+        let image = self.image();
+        verify_receipt(receipt, image.image_id())?;
+        image.decode(receipt)
+    }
+}
+
+// Last stored block with its receipt, None before the genesis block is proven. Receipts
+// are verified once by store_block, here the header is only decoded from the journal.
+pub async fn head_block(
+    db: &DatabaseConnection
+) -> Result<Option<(BlockKind, Receipt, BlockHeader)>, String> {
+    Ok(
+        head_block_roots(db).await?.map(|(kind, receipt, header, _)| (kind, receipt, header))
+    )
+}
+
+async fn head_block_roots(
+    db: &DatabaseConnection
+) -> Result<Option<(BlockKind, Receipt, BlockHeader, TableRoots)>, String> {
+    let Some(head) = state_block::Entity
+        ::find()
+        .order_by_desc(state_block::Column::Height)
+        .one(db).await
+        .map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let kind = BlockKind::parse(&head.kind)?;
+    let receipt: Receipt = serde_json::from_value(head.receipt).map_err(|e| e.to_string())?;
+    let (header, roots) = kind.image().decode(&receipt)?;
+    Ok(Some((kind, receipt, header, roots)))
+}
+
+// Header of the last stored block, None before the genesis block is proven.
+pub async fn chain_head(db: &DatabaseConnection) -> Result<Option<BlockHeader>, String> {
+    Ok(head_block(db).await?.map(|(_, _, header)| header))
+}
+
+// Header of the last stored block with the roots of its three tables, None before the
+// genesis block is proven.
+pub async fn head_roots(
+    db: &DatabaseConnection
+) -> Result<Option<(BlockHeader, TableRoots)>, String> {
+    Ok(head_block_roots(db).await?.map(|(_, _, header, roots)| (header, roots)))
+}
+
+// Parent of the next transition or delta, which can only extend an existing chain.
+pub async fn parent_block(db: &DatabaseConnection) -> Result<BlockHeader, ApiError> {
+    chain_head(db).await
+        .map_err(ApiError::Internal)?
+        .ok_or_else(|| {
+            ApiError::Conflict(
+                "No genesis block yet, its proof is queued at startup or by GET /api/db/init".to_string()
+            )
+        })
+}
+
+// Queues the proof of the genesis block when the chain has none, so that transitions have
+// a parent once a worker has proven it. Returns the id of the genesis job, an already
+// queued or running one is not queued twice.
+pub async fn bootstrap_genesis(db: &DatabaseConnection) -> Result<Option<i32>, ApiError> {
+    if chain_head(db).await.map_err(ApiError::Internal)?.is_some() {
+        return Ok(None);
+    }
+    let pending = proof_job::Entity
+        ::find()
+        .filter(proof_job::Column::Kind.eq(JobKind::InitOverall.as_str()))
+        .filter(
+            proof_job::Column::Status.is_in([JobStatus::Queued.as_str(), JobStatus::Running.as_str()])
+        )
+        .one(db).await?;
+    if let Some(job) = pending {
+        return Ok(Some(job.id));
+    }
+    let id = enqueue_job(db, JobKind::InitOverall, None, ReceiptKind::default()).await?;
+    Ok(Some(id))
+}
+
+// Verifies a proven block against the image of its kind and stores it, so that reading
+// the head later does not verify its receipt again. Its height is the primary key, so a
+// block racing another one onto the same parent is refused.
+pub async fn store_block<C: ConnectionTrait>(
+    db: &C,
+    kind: BlockKind,
+    header: &BlockHeader,
+    receipt: &Receipt
+) -> Result<(), ApiError> {
+    use sea_orm::ActiveValue::Set;
+    if &kind.verify(receipt).map_err(ApiError::Verification)? != header {
+        return Err(ApiError::Verification("Block does not match its receipt".to_string()));
+    }
+    let block = state_block::ActiveModel {
+        height: Set(header.height as i64),
+        parent_root: Set(header.parent_root.to_string()),
+        root: Set(header.root.to_string()),
+        kind: Set(kind.as_str().to_string()),
+        receipt: Set(json!(receipt)),
+        previous_updated_at: Set(header.previous_updated_at),
+        updated_at: Set(header.updated_at),
+        created_at: Set(Utc::now().naive_utc()),
+    };
+    block.insert(db).await.map_err(|e| ApiError::Conflict(e.to_string()))?;
+    Ok(())
+}
+
+// Checks a stored row against the header committed by its receipt.
+fn verify_block(block: &state_block::Model) -> Result<BlockHeader, String> {
+    let receipt: Receipt = serde_json
+        ::from_value(block.receipt.clone())
+        .map_err(|e| e.to_string())?;
+    let header = BlockKind::parse(&block.kind)?.verify(&receipt)?;
+    if
+        header.height as i64 != block.height ||
+        header.parent_root.to_string() != block.parent_root ||
+        header.root.to_string() != block.root
+    {
+        return Err(format!("Block {} does not match its receipt", block.height));
+    }
+    Ok(header)
+}
+
+const DEFAULT_BLOCK_LIMIT: u64 = 50;
+const MAX_BLOCK_LIMIT: u64 = 200;
+
+#[derive(Deserialize)]
+pub struct BlockQuery {
+    limit: Option<u64>,
+    #[serde(default)]
+    offset: u64,
+}
+
+// Handler to list a page of the stored blocks from genesis on, without their receipts
+pub async fn get_blocks_handler(
+    axum::extract::Query(query): axum::extract::Query<BlockQuery>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_BLOCK_LIMIT);
+    if !(1..=MAX_BLOCK_LIMIT).contains(&limit) {
+        return Err(ApiError::Validation(format!("limit must be between 1 and {}", MAX_BLOCK_LIMIT)));
+    }
+    let headers = state_block::Entity
+        ::find()
+        .select_only()
+        .columns([
+            state_block::Column::Height,
+            state_block::Column::ParentRoot,
+            state_block::Column::Root,
+            state_block::Column::Kind,
+            state_block::Column::UpdatedAt,
+        ])
+        .order_by_asc(state_block::Column::Height)
+        .limit(limit)
+        .offset(query.offset)
+        .into_json()
+        .all(&*db).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "limit": limit,
+        "offset": query.offset,
+        "data": headers
+    })))
+}
+
+// Handler to walk the chain from genesis, verifying every receipt and parent link
+pub async fn verify_blocks_handler(axum::extract::State(
+    db,
+): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
+    Json<Value>,
+    ApiError
+> {
+    let blocks = state_block::Entity
+        ::find()
+        .order_by_asc(state_block::Column::Height)
+        .all(&*db).await?;
+
+    let headers = blocks
+        .iter()
+        .map(verify_block)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ApiError::Verification)?;
+    verify_blocks(&headers).map_err(ApiError::Verification)?;
+
+    let head = headers
+        .last()
+        .ok_or_else(|| ApiError::Verification("The chain has no blocks".to_string()))?;
+    Ok(Json(json!({
+        "status": "success",
+        "verified": true,
+        "height": head.height,
+        "root": head.root,
+        "updated_at": head.updated_at
+    })))
+}
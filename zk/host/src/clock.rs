@@ -0,0 +1,7 @@
+use chrono::Utc;
+
+/// Timestamp of the next proven state, following the block at `previous_updated_at`.
+/// Taken from the host clock, but never earlier than the parent block.
+pub fn next_timestamp(previous_updated_at: i64) -> i64 {
+    Utc::now().timestamp().max(previous_updated_at)
+}
@@ -1,142 +1,111 @@
-use axum::{ http::StatusCode, Json };
-use oil_tokenization_core::CommentState;
+use axum::{ http::StatusCode, response::{ IntoResponse, Response }, Json };
+use oil_tokenization_core::{ Actor, CommentAction, CommentRecord, Leaf, Record };
 use chrono::Utc;
 use entity::{ comment, CommentModel };
-use ethers::types::Address;
-use methods::{ INIT_COMMENT_ELF, INIT_COMMENT_ID };
-use risc0_zkvm::{ default_prover, ExecutorEnv, Receipt };
-use sea_orm::{ ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait, QueryOrder };
-use serde::{ Deserialize, Serialize };
+use sea_orm::{ ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder };
 use serde_json::{ Value, json };
 use std::sync::Arc;
 
-use crate::{ auth::USER, jwt::Claims, SessionStats };
-
-pub fn get_comment_leaves(comments: &Vec<CommentModel>) -> Vec<String> {
-    let mut leaves = vec![];
-    for comment in comments {
-        let comment_record = format!(
-            "{}:{}:{}:{}:{}:{}",
-            comment.id,
-            comment.oil_token_id,
-            comment.user,
-            comment.content,
-            comment.created_at.and_utc().timestamp(),
-            comment.updated_at.and_utc().timestamp()
-        );
-        leaves.push(comment_record);
+use crate::{
+    auth::current_user,
+    dry_run::{ dry_run_transition, DryRunQuery },
+    error::ApiError,
+    job::{ enqueue_response, JobKind, JobStatus, ReceiptQuery },
+    prover::ProverBackend,
+    state::{ init_sub_state, SubStateCommit },
+    transition::{ next_id, queue_transition, RowInsert, StateTransition },
+};
+
+pub fn to_comment_record(comment: &CommentModel) -> CommentRecord {
+    CommentRecord {
+        id: comment.id,
+        oil_token_id: comment.oil_token_id,
+        user: comment.user.clone(),
+        content: comment.content.clone(),
+        created_at: comment.created_at.and_utc().timestamp(),
+        updated_at: comment.updated_at.and_utc().timestamp(),
     }
-    leaves
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct CommentCommit {
-    pub receipt: Receipt,
-    pub stats: SessionStats,
+// Rows as (primary key, leaf), laid out in the table tree by slot_leaves.
+pub fn get_comment_leaves(comments: &[CommentModel]) -> Vec<(i32, Leaf)> {
+    comments
+        .iter()
+        .map(|comment| (comment.id, to_comment_record(comment).leaf()))
+        .collect()
 }
 
-impl CommentCommit {
-    pub fn get_commit(&self) -> Result<CommentState, String> {
-        let state = self.receipt.journal.decode().map_err(|e| e.to_string())?;
-        Ok(state)
-    }
-
-    pub fn verify_and_get_commit(&self) -> Result<CommentState, String> {
-        self.receipt.verify(INIT_COMMENT_ID).map_err(|e| e.to_string())?;
-        self.get_commit()
-    }
-}
+pub type CommentCommit = SubStateCommit<CommentAction>;
 
-pub fn init_comment(leaves: Vec<String>) -> Result<CommentCommit, String> {
-    let env = ExecutorEnv::builder().write(&leaves).unwrap().build().unwrap();
-    let prover = default_prover();
-    let prove_info = prover.prove(env, INIT_COMMENT_ELF).unwrap();
-    let comment_commit = CommentCommit {
-        receipt: prove_info.receipt,
-        stats: SessionStats {
-            segments: prove_info.stats.segments,
-            total_cycles: prove_info.stats.total_cycles,
-            user_cycles: prove_info.stats.user_cycles,
-            paging_cycles: prove_info.stats.paging_cycles,
-            reserved_cycles: prove_info.stats.reserved_cycles,
-        },
-    };
-    Ok(comment_commit)
+pub fn init_comment(prover: &dyn ProverBackend, leaves: Vec<Leaf>) -> Result<CommentCommit, String> {
+    init_sub_state(prover, leaves)
 }
 
-pub async fn init_comment_handler(axum::extract::State(
-    db,
-): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
-    Json<Value>,
-    (axum::http::StatusCode, String)
-> {
-    let comments = get_all_comments(&db).await.unwrap();
-    let leaves = get_comment_leaves(&comments);
-    let result = init_comment(leaves).map_err(|e| (
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-        e.to_string(),
-    ))?;
-    Ok(Json(json!(result)))
+// Handler to queue the proof of the comments table, see /api/jobs/{id}
+pub async fn init_comment_handler(
+    axum::extract::Query(query): axum::extract::Query<ReceiptQuery>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    enqueue_response(&db, JobKind::InitComment, None, query.receipt_kind).await
 }
 
 #[axum::debug_handler]
 pub async fn create_comment(
+    axum::extract::Query(query): axum::extract::Query<DryRunQuery>,
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>,
     Json(comment_data): Json<CommentModel>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
-    let current_user = USER.with(|user| Claims {
-        username: user.username.clone(),
-        addr: user.addr
-            .clone()
-            .parse::<Address>()
-            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid address".to_string()))
-            .unwrap()
-            .to_string(),
-        exp: user.exp,
-    });
-
-    eprintln!("Request from user: {:?}", current_user.addr);
-    eprintln!("Request from username: {}", current_user.username);
+) -> Result<Response, ApiError> {
+    let current_user = current_user()?;
 
-    use sea_orm::ActiveValue::Set;
-    let comment_id = comment::Entity
-        ::find()
-        .order_by_desc(comment::Column::Id)
-        .one(&*db).await
-        .unwrap()
-        .unwrap();
+    // the id is taken after the state is loaded, see next_id
+    let mut transition = StateTransition::load(&db).await?;
+    let comment_id = next_id(&db, "comment").await?;
     let now_naive: chrono::NaiveDateTime = Utc::now().naive_utc();
-    let comment_model = comment::ActiveModel {
-        id: Set(comment_id.id + 1),
-        oil_token_id: Set(comment_data.oil_token_id),
-        user: Set(current_user.addr),
-        content: Set(comment_data.content.to_owned()),
-        created_at: Set(now_naive.clone()),
-        updated_at: Set(now_naive),
-        ..Default::default()
+    let new_comment = CommentModel {
+        id: comment_id,
+        oil_token_id: comment_data.oil_token_id,
+        user: current_user.addr,
+        content: comment_data.content,
+        created_at: now_naive,
+        updated_at: now_naive,
     };
 
-    comment_model
-        .insert(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    Ok(Json(json!({
-      "status": "success",
-      "message": "Comment created successfully"
-  })))
+    // prove the CREATE transition before the row is written
+    transition
+        .comment(CommentAction::CREATE, None, Some(to_comment_record(&new_comment)))
+        .map_err(ApiError::Validation)?;
+    // a dry run stops here, with what the proof would cost
+    if query.dry_run {
+        return Ok(dry_run_transition(&db, transition, Actor::SYSTEM).await?.into_response());
+    }
+    // proven by a prover worker, which stores the row with its block
+    let job_id = queue_transition(
+        &db,
+        transition,
+        Actor::SYSTEM,
+        RowInsert::Comment(Box::new(new_comment))
+    ).await?;
+
+    let body = json!({
+        "status": "success",
+        "message": "Comment queued, it is stored once job_id is done",
+        "id": comment_id,
+        "job_id": job_id,
+        "job_status": JobStatus::Queued.as_str()
+    });
+    Ok((StatusCode::ACCEPTED, Json(body)).into_response())
 }
 
 // Handler to get comments by oil token ID
 pub async fn get_comment_by_oil_token_id(
     axum::extract::Path(id): axum::extract::Path<i32>,
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<Value>, ApiError> {
     let comments = comment::Entity
         ::find()
         .filter(comment::Column::OilTokenId.eq(id))
         .order_by_desc(comment::Column::CreatedAt)
-        .all(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .all(&*db).await?;
 
     Ok(Json(json!({
         "status": "success",
@@ -149,12 +118,9 @@ pub async fn get_comments(axum::extract::State(
     db,
 ): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
     Json<Value>,
-    (axum::http::StatusCode, String)
+    ApiError
 > {
-    let comments = get_all_comments(&db).await.map_err(|e| (
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-        e.to_string(),
-    ))?;
+    let comments = get_all_comments(&db).await?;
 
     Ok(Json(json!({
         "status": "success",
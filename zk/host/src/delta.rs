@@ -0,0 +1,368 @@
+use axum::Json;
+use chrono::Utc;
+use entity::synced_state;
+use oil_tokenization_core::{
+    merkle::hash_leaf,
+    BlockHeader,
+    DeltaParams,
+    Leaf,
+    DeltaState,
+    LeafUpdate,
+    OverallState,
+    slot_leaves,
+    StateTable,
+    TableRoots,
+};
+use methods::SYNC_DELTA_ID;
+use risc0_zkvm::{ sha::Digest, Receipt };
+use serde::{ Deserialize, Serialize };
+use sea_orm::{
+    sea_query::OnConflict,
+    ColumnTrait,
+    ConnectionTrait,
+    DatabaseConnection,
+    DbErr,
+    EntityTrait,
+    QueryFilter,
+    TransactionTrait,
+};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::{
+    block::{ chain_head, store_block, BlockKind },
+    clock::next_timestamp,
+    error::ApiError,
+    job::{ enqueue_response, JobKind, ReceiptQuery },
+    overall::{
+        get_all_comments,
+        get_all_oil_tokens,
+        get_all_tokenizations,
+        get_comment_leaves,
+        get_oil_token_leaves,
+        get_tokenization_leaves,
+        commit_overall,
+        OverallCommit,
+    },
+    prover::{ verify_receipt, Guest, ProveRequest, ProverBackend, SharedProver },
+    transition::StagedTable,
+    SessionStats,
+};
+
+// Leaves of a block proven by a sync. A delta sync only proves the rows that changed since
+// the synced leaves of the chain head, they are stored so that any worker finds them. Only
+// the leaves of the latest synced block are kept.
+#[derive(Clone)]
+pub struct SyncedState {
+    oil_token: StagedTable,
+    tokenization: StagedTable,
+    comment: StagedTable,
+}
+
+// Current rows of the three committed tables, as (primary key, leaf) in id order.
+#[derive(Serialize, Deserialize)]
+pub struct TableLeaves {
+    pub oil_token: Vec<(i32, Leaf)>,
+    pub tokenization: Vec<(i32, Leaf)>,
+    pub comment: Vec<(i32, Leaf)>,
+}
+
+impl TableLeaves {
+    // Leaves of one table at the slots of their rows, the input of the guests that init it.
+    pub fn leaves(rows: &[(i32, Leaf)]) -> Result<Vec<Leaf>, String> {
+        slot_leaves(rows)
+    }
+
+    pub async fn load(db: &sea_orm::DatabaseConnection) -> Result<Self, sea_orm::DbErr> {
+        let oil_tokens = get_all_oil_tokens(db).await?;
+        let tokenizations = get_all_tokenizations(db).await?;
+        let comments = get_all_comments(db).await?;
+        Ok(TableLeaves {
+            oil_token: get_oil_token_leaves(&oil_tokens),
+            tokenization: get_tokenization_leaves(&tokenizations),
+            comment: get_comment_leaves(&comments),
+        })
+    }
+}
+
+// Stored form of the synced leaves, as (primary key, leaf hash) in slot order.
+#[derive(Serialize, Deserialize)]
+struct SyncedLeaves {
+    oil_token: Vec<(i32, Digest)>,
+    tokenization: Vec<(i32, Digest)>,
+    comment: Vec<(i32, Digest)>,
+}
+
+
+// Stages every changed, deleted and new row of one table and records its update.
+fn diff_table(
+    table: StateTable,
+    synced: &mut StagedTable,
+    rows: &[(i32, Leaf)],
+    updates: &mut Vec<LeafUpdate>
+) -> Result<(), String> {
+    let mut current: BTreeMap<i32, Leaf> = rows.iter().cloned().collect();
+
+    // changed and deleted rows, a deleted row empties its slot
+    for (id, old_hash) in synced.rows() {
+        let new_leaf = current.remove(&id);
+        if new_leaf.as_ref().map(|leaf| hash_leaf(leaf)) == Some(old_hash) {
+            continue;
+        }
+        let (proof, old_leaf) = synced.stage(id, new_leaf.clone())?;
+        updates.push(LeafUpdate { table, proof, old_leaf, new_leaf });
+    }
+
+    // new rows fill the empty slots of their ids
+    for (id, leaf) in current {
+        let (proof, old_leaf) = synced.stage(id, Some(leaf.clone()))?;
+        updates.push(LeafUpdate { table, proof, old_leaf, new_leaf: Some(leaf) });
+    }
+    Ok(())
+}
+
+impl SyncedState {
+    pub fn new(leaves: &TableLeaves) -> Result<Self, String> {
+        Ok(SyncedState {
+            oil_token: StagedTable::new(&leaves.oil_token)?,
+            tokenization: StagedTable::new(&leaves.tokenization)?,
+            comment: StagedTable::new(&leaves.comment)?,
+        })
+    }
+
+    // Synced leaves of `block`, None when no sync stored them or they do not add up to its root.
+    pub async fn load(db: &DatabaseConnection, block: &BlockHeader) -> Result<Option<Self>, String> {
+        let Some(row) = synced_state::Entity
+            ::find_by_id(block.height as i64)
+            .one(db).await
+            .map_err(|e| e.to_string())? else {
+            return Ok(None);
+        };
+        let leaves: SyncedLeaves = serde_json::from_value(row.leaves).map_err(|e| e.to_string())?;
+        let synced = SyncedState {
+            oil_token: StagedTable::from_hashes(leaves.oil_token)?,
+            tokenization: StagedTable::from_hashes(leaves.tokenization)?,
+            comment: StagedTable::from_hashes(leaves.comment)?,
+        };
+        Ok(Some(synced).filter(|synced| synced.root() == block.root))
+    }
+
+    // Stores the leaves as those of `block` and drops those of every earlier block, which no
+    // delta sync starts from anymore.
+    pub async fn store<C: ConnectionTrait>(&self, db: &C, block: &BlockHeader) -> Result<(), DbErr> {
+        use sea_orm::ActiveValue::Set;
+        let leaves = SyncedLeaves {
+            oil_token: self.oil_token.rows(),
+            tokenization: self.tokenization.rows(),
+            comment: self.comment.rows(),
+        };
+        let row = synced_state::ActiveModel {
+            height: Set(block.height as i64),
+            root: Set(block.root.to_string()),
+            leaves: Set(serde_json::to_value(leaves).map_err(|e| DbErr::Custom(e.to_string()))?),
+            created_at: Set(Utc::now().naive_utc()),
+        };
+        synced_state::Entity
+            ::insert(row)
+            .on_conflict(
+                OnConflict::column(synced_state::Column::Height)
+                    .update_columns([
+                        synced_state::Column::Root,
+                        synced_state::Column::Leaves,
+                        synced_state::Column::CreatedAt,
+                    ])
+                    .to_owned()
+            )
+            .exec(db).await?;
+        synced_state::Entity
+            ::delete_many()
+            .filter(synced_state::Column::Height.lt(block.height as i64))
+            .exec(db).await?;
+        Ok(())
+    }
+
+    pub fn root(&self) -> Digest {
+        OverallState::compute_root(
+            &self.oil_token.root().root,
+            &self.tokenization.root().root,
+            &self.comment.root().root
+        )
+    }
+
+    /// Moves the synced leaves to `leaves` and returns the guest input proving that move on top of `parent`.
+    pub fn diff(&mut self, leaves: &TableLeaves, parent: BlockHeader) -> Result<DeltaParams, String> {
+        let roots = TableRoots {
+            oil_token: self.oil_token.root(),
+            tokenization: self.tokenization.root(),
+            comment: self.comment.root(),
+        };
+
+        let mut updates = vec![];
+        diff_table(StateTable::OilToken, &mut self.oil_token, &leaves.oil_token, &mut updates)?;
+        diff_table(
+            StateTable::Tokenization,
+            &mut self.tokenization,
+            &leaves.tokenization,
+            &mut updates
+        )?;
+        diff_table(StateTable::Comment, &mut self.comment, &leaves.comment, &mut updates)?;
+
+        Ok(DeltaParams {
+            parent,
+            timestamp: next_timestamp(parent.updated_at),
+            roots,
+            updates,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeltaCommit {
+    pub receipt: Receipt,
+    pub stats: SessionStats,
+}
+
+impl DeltaCommit {
+    pub fn get_commit(&self) -> Result<DeltaState, String> {
+        let state = self.receipt.journal.decode().map_err(|e| e.to_string())?;
+        Ok(state)
+    }
+
+    pub fn verify_and_get_commit(&self) -> Result<DeltaState, String> {
+        verify_receipt(&self.receipt, SYNC_DELTA_ID)?;
+        self.get_commit()
+    }
+}
+
+pub fn prove_delta(prover: &dyn ProverBackend, params: DeltaParams) -> Result<DeltaCommit, String> {
+    let proof = prover.prove(&ProveRequest::new(Guest::SyncDelta, &params)?)?;
+    let delta_commit = DeltaCommit {
+        receipt: proof.receipt,
+        stats: proof.stats,
+    };
+    Ok(delta_commit)
+}
+
+// Proves the full state on top of the chain head and stores its block together with
+// its leaves, which later delta syncs start from.
+pub async fn commit_synced(
+    db: &DatabaseConnection,
+    prover: SharedProver,
+    leaves: &TableLeaves
+) -> Result<OverallCommit, ApiError> {
+    let synced = SyncedState::new(leaves).map_err(ApiError::Internal)?;
+    let overall_commit = commit_overall(
+        db,
+        prover,
+        TableLeaves::leaves(&leaves.oil_token).map_err(ApiError::Internal)?,
+        TableLeaves::leaves(&leaves.tokenization).map_err(ApiError::Internal)?,
+        TableLeaves::leaves(&leaves.comment).map_err(ApiError::Internal)?
+    ).await?;
+    let state = overall_commit.get_commit().map_err(ApiError::Internal)?;
+    synced.store(db, &state.block).await?;
+    Ok(overall_commit)
+}
+
+// Receipt of a delta sync, which is a full proof when the chain head has no synced leaves.
+pub enum SyncOutcome {
+    Full(OverallCommit),
+    Delta {
+        commit: DeltaCommit,
+        updates: usize,
+    },
+}
+
+// Proves the rows changed since the synced leaves of the chain head and stores the block
+// together with the moved leaves. Without synced leaves for the head, as after a transition,
+// the full state is proven instead.
+pub async fn sync_delta(
+    db: &DatabaseConnection,
+    prover: SharedProver
+) -> Result<SyncOutcome, ApiError> {
+    let leaves = TableLeaves::load(db).await?;
+
+    let parent = chain_head(db).await.map_err(ApiError::Internal)?;
+    let synced = match &parent {
+        Some(parent) => SyncedState::load(db, parent).await.map_err(ApiError::Internal)?,
+        None => None,
+    };
+    let (Some(mut synced), Some(parent)) = (synced, parent) else {
+        return Ok(SyncOutcome::Full(commit_synced(db, prover, &leaves).await?));
+    };
+
+    let params = synced
+        .diff(&leaves, parent)
+        .map_err(ApiError::Internal)?;
+    let updates = params.updates.len();
+    let delta_commit = tokio::task
+        ::spawn_blocking(move || prove_delta(&*prover, params))
+        .await?
+        .map_err(ApiError::Prover)?;
+    let state = delta_commit.get_commit().map_err(ApiError::Internal)?;
+
+    // the block and its leaves are stored together, a block another write got onto the
+    // same parent first is refused
+    let txn = db.begin().await?;
+    store_block(&txn, BlockKind::Delta, &state.block, &delta_commit.receipt).await?;
+    synced.store(&txn, &state.block).await?;
+    txn.commit().await?;
+
+    Ok(SyncOutcome::Delta { commit: delta_commit, updates })
+}
+
+// Handler to queue a sync proving only the rows changed since the last synced block, see /api/jobs/{id}
+pub async fn sync_delta_handler(
+    axum::extract::Query(query): axum::extract::Query<ReceiptQuery>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    enqueue_response(&db, JobKind::SyncDelta, None, query.receipt_kind).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oil_tokenization_core::LeafEncoder;
+
+    fn rows(table: StateTable, ids: &[i32]) -> Vec<(i32, Leaf)> {
+        ids.iter()
+            .map(|id| (*id, LeafEncoder::new(table).i32(*id).str("row").finish()))
+            .collect()
+    }
+
+    #[test]
+    fn stored_leaves_diff_like_the_tables_they_were_taken_from() {
+        let before = TableLeaves {
+            oil_token: rows(StateTable::OilToken, &[1, 2]),
+            tokenization: rows(StateTable::Tokenization, &[1]),
+            comment: rows(StateTable::Comment, &[1, 2, 3]),
+        };
+        let synced = SyncedState::new(&before).unwrap();
+        let stored = SyncedLeaves {
+            oil_token: synced.oil_token.rows(),
+            tokenization: synced.tokenization.rows(),
+            comment: synced.comment.rows(),
+        };
+        let stored: SyncedLeaves = serde_json
+            ::from_value(serde_json::to_value(stored).unwrap())
+            .unwrap();
+        let mut loaded = SyncedState {
+            oil_token: StagedTable::from_hashes(stored.oil_token).unwrap(),
+            tokenization: StagedTable::from_hashes(stored.tokenization).unwrap(),
+            comment: StagedTable::from_hashes(stored.comment).unwrap(),
+        };
+        assert_eq!(loaded.root(), synced.root());
+
+        let after = TableLeaves {
+            oil_token: rows(StateTable::OilToken, &[1, 2, 3]),
+            tokenization: vec![],
+            comment: rows(StateTable::Comment, &[1, 3]),
+        };
+        let params = loaded.diff(&after, BlockHeader::genesis(loaded.root(), 0)).unwrap();
+        assert_eq!(params.updates.len(), 3);
+        assert_eq!(loaded.root(), SyncedState::new(&after).unwrap().root());
+        // the deleted comment only clears its own slot
+        let deleted = &params.updates[2];
+        assert_eq!((deleted.proof.index, deleted.new_leaf.clone()), (1, None));
+    }
+}
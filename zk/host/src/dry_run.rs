@@ -0,0 +1,122 @@
+use axum::Json;
+use dotenv::dotenv;
+use oil_tokenization_core::Actor;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use serde_json::{ Value, json };
+use std::env;
+use std::sync::Arc;
+
+use crate::{
+    block::chain_head,
+    delta::TableLeaves,
+    error::ApiError,
+    overall::init_overall,
+    proof::decode_journal,
+    prover::{ ExecutingProver, Execution, Guest },
+    transition::{ head_params, prove_transition, StateTransition },
+};
+
+// `?dry_run=true` on a write runs its transition in the executor and writes nothing.
+#[derive(Deserialize)]
+pub struct DryRunQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// Cycle budget of `guest` in total cycles, from CYCLE_BUDGET_<GUEST>, such as
+// CYCLE_BUDGET_INIT_OVERALL, or else from CYCLE_BUDGET. Unset means no budget.
+fn cycle_budget(guest: Guest) -> Option<u64> {
+    dotenv().ok();
+    env::var(format!("CYCLE_BUDGET_{}", guest.as_str().to_uppercase()))
+        .or_else(|_| env::var("CYCLE_BUDGET"))
+        .ok()
+        .and_then(|budget| budget.parse().ok())
+}
+
+// Every execution with its journal and stats, and a warning for each one over its budget.
+fn report(executions: Vec<Execution>) -> Result<Json<Value>, ApiError> {
+    let mut runs = vec![];
+    let mut warnings = vec![];
+    let mut total_cycles = 0;
+    for execution in executions {
+        let guest = execution.guest;
+        let budget = cycle_budget(guest);
+        if let Some(budget) = budget.filter(|budget| execution.stats.total_cycles > *budget) {
+            let warning = format!(
+                "{} needs {} cycles, over its budget of {}",
+                guest.as_str(),
+                execution.stats.total_cycles,
+                budget
+            );
+            tracing::warn!("Dry run: {}", warning);
+            warnings.push(warning);
+        }
+        let journal = decode_journal(guest, &execution.journal).map_err(ApiError::Internal)?;
+        total_cycles += execution.stats.total_cycles;
+        runs.push(
+            json!({
+            "guest": guest,
+            "journal": journal,
+            "stats": execution.stats,
+            "cycle_budget": budget
+        })
+        );
+    }
+
+    Ok(
+        Json(
+            json!({
+        "status": "success",
+        "dry_run": true,
+        "executions": runs,
+        "total_cycles": total_cycles,
+        "warnings": warnings
+    })
+        )
+    )
+}
+
+// Handler to run the proof of the full state in the executor, to see what it will cost
+pub async fn init_overall_dry_run_handler(axum::extract::State(
+    db,
+): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
+    Json<Value>,
+    ApiError
+> {
+    let leaves = TableLeaves::load(&db).await?;
+    let parent = chain_head(&db).await.map_err(ApiError::Internal)?;
+    let executions = tokio::task
+        ::spawn_blocking(move || {
+            let prover = ExecutingProver::default();
+            init_overall(
+                &prover,
+                TableLeaves::leaves(&leaves.oil_token)?,
+                TableLeaves::leaves(&leaves.tokenization)?,
+                TableLeaves::leaves(&leaves.comment)?,
+                parent
+            )?;
+            Ok::<_, String>(prover.into_executions())
+        })
+        .await?
+        .map_err(ApiError::Prover)?;
+    report(executions)
+}
+
+// Runs `transition` on top of the chain head in the executor, in place of committing it.
+pub async fn dry_run_transition(
+    db: &DatabaseConnection,
+    transition: StateTransition,
+    actor: Actor
+) -> Result<Json<Value>, ApiError> {
+    let params = head_params(db, transition, actor).await?;
+    let executions = tokio::task
+        ::spawn_blocking(move || {
+            let prover = ExecutingProver::default();
+            prove_transition(&prover, params)?;
+            Ok::<_, String>(prover.into_executions())
+        })
+        .await?
+        .map_err(ApiError::Prover)?;
+    report(executions)
+}
@@ -0,0 +1,247 @@
+use axum::{
+    body::{ to_bytes, Body },
+    extract::Request,
+    http::{ HeaderMap, Method },
+    middleware::Next,
+    response::Response,
+};
+use dotenv::dotenv;
+use oil_tokenization_core::{
+    address_hex,
+    recover_typed_signer,
+    Eip712Domain,
+    Login,
+    MarketAction,
+    SiweMessage,
+    StateTable,
+    TypedData,
+};
+use serde_json::{ json, Value };
+use std::env;
+
+use crate::{ auth::USER, error::ApiError, redis::use_action_nonce, siwe::SiweConfig };
+
+// Largest request body a signed action may have, it is buffered to be hashed.
+const MAX_ACTION_BODY_BYTES: usize = 64 * 1024;
+// How far ahead an action deadline may be, its nonce is remembered until then.
+const MAX_ACTION_TTL_SECONDS: i64 = 3600;
+
+// Fields of an encodeType string, as the `types` entry of eth_signTypedData_v4.
+fn type_fields(encode_type: &str) -> Value {
+    let fields = encode_type
+        .split_once('(')
+        .and_then(|(_, fields)| fields.strip_suffix(')'))
+        .unwrap_or_default();
+    fields
+        .split(',')
+        .filter_map(|field| field.split_once(' '))
+        .map(|(ty, name)| json!({ "name": name, "type": ty }))
+        .collect()
+}
+
+// eth_signTypedData_v4 payload of `message` under the platform domain.
+pub fn typed_data<T: TypedData>(domain: &Eip712Domain, message: Value) -> Value {
+    json!({
+        "types": {
+            "EIP712Domain": type_fields(Eip712Domain::TYPE),
+            T::NAME: type_fields(T::TYPE)
+        },
+        "primaryType": T::NAME,
+        "domain": {
+            "name": domain.name,
+            "version": domain.version,
+            "chainId": domain.chain_id
+        },
+        "message": message
+    })
+}
+
+// Typed-data form of a SIWE login message, for wallets that sign typed data.
+pub fn login_typed_data(message: &SiweMessage, chain_id: u64) -> Value {
+    let login = Login::from(message);
+    typed_data::<Login>(
+        &Eip712Domain::new(chain_id),
+        json!({
+            "wallet": login.wallet,
+            "domain": login.domain,
+            "statement": login.statement,
+            "uri": login.uri,
+            "version": login.version,
+            "nonce": login.nonce,
+            "issuedAt": login.issued_at,
+            "expirationTime": login.expiration_time,
+            "notBefore": login.not_before
+        })
+    )
+}
+
+// Committed table and action a protected route writes to, None for other routes.
+fn market_action(method: &Method, path: &str) -> Option<(StateTable, &'static str)> {
+    if method != Method::POST {
+        return None;
+    }
+    match path {
+        "/api/oil_tokens" => Some((StateTable::OilToken, "CREATE")),
+        "/api/tokenizations" => Some((StateTable::Tokenization, "CREATE")),
+        "/api/comments" => Some((StateTable::Comment, "CREATE")),
+        _ if path.starts_with("/api/tokenizations/") && path.ends_with("/complete") =>
+            Some((StateTable::Tokenization, "UPDATE")),
+        _ => None,
+    }
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+// Whether unsigned marketplace writes pass, from ALLOW_UNSIGNED_ACTIONS. Only local
+// development opts in to them.
+fn unsigned_actions_allowed() -> bool {
+    dotenv().ok();
+    env::var("ALLOW_UNSIGNED_ACTIONS").is_ok_and(|v| v == "1" || v == "true")
+}
+
+// Checks the EIP-712 `MarketAction` signature of a marketplace request, sent as the
+// `x-action-signature`, `x-action-nonce` and `x-action-deadline` headers. It must be
+// signed by the logged in wallet over the exact request body, and each nonce is accepted
+// once. Unsigned requests are refused unless ALLOW_UNSIGNED_ACTIONS is set. Runs inside
+// `auth`, which sets USER.
+pub async fn signed_action(req: Request, next: Next) -> Result<Response, ApiError> {
+    let Some((table, action)) = market_action(req.method(), req.uri().path()) else {
+        return Ok(next.run(req).await);
+    };
+    let (Some(signature), Some(nonce), Some(deadline)) = (
+        header(req.headers(), "x-action-signature"),
+        header(req.headers(), "x-action-nonce"),
+        header(req.headers(), "x-action-deadline"),
+    ) else {
+        if unsigned_actions_allowed() {
+            return Ok(next.run(req).await);
+        }
+        return Err(ApiError::Unauthorized("Missing action signature".to_string()));
+    };
+    if !(8..=64).contains(&nonce.len()) || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(
+            ApiError::Validation("Action nonce must be 8 to 64 alphanumeric characters".to_string())
+        );
+    }
+    let deadline: i64 = deadline
+        .parse()
+        .map_err(|_| ApiError::Validation("Invalid action deadline".to_string()))?;
+    let now = chrono::Utc::now().timestamp();
+    if deadline < now {
+        return Err(ApiError::Unauthorized("Action signature has expired".to_string()));
+    }
+    if deadline > now + MAX_ACTION_TTL_SECONDS {
+        return Err(ApiError::Validation("Action deadline is too far ahead".to_string()));
+    }
+
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, MAX_ACTION_BODY_BYTES).await.map_err(|_|
+        ApiError::Validation("Request body is too large".to_string())
+    )?;
+    let actor = USER.try_with(|user| user.addr.to_ascii_lowercase()).map_err(|_|
+        ApiError::Unauthorized("Not logged in".to_string())
+    )?;
+    let market_action = MarketAction {
+        actor: actor.clone(),
+        table,
+        action: action.to_string(),
+        content: MarketAction::content_hash(&body),
+        nonce,
+        deadline,
+    };
+    let signer = recover_typed_signer(
+        &Eip712Domain::new(SiweConfig::from_env().chain_id),
+        &market_action,
+        &signature
+    ).map_err(ApiError::Unauthorized)?;
+    if address_hex(&signer) != actor {
+        return Err(ApiError::Unauthorized("Action is not signed by the actor".to_string()));
+    }
+    // the nonce is only spent by a valid signature, and kept until the deadline has passed
+    if !use_action_nonce(&actor, &market_action.nonce, (deadline - now + 1) as u64).await? {
+        return Err(ApiError::Unauthorized("Action was already submitted".to_string()));
+    }
+
+    Ok(next.run(Request::from_parts(parts, Body::from(body))).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{
+        signers::{ LocalWallet, Signer },
+        types::{ transaction::eip712::{ Eip712, TypedData as EthersTypedData }, H256 },
+    };
+    use oil_tokenization_core::{
+        fixtures::{ market_action, siwe_message, NOW, WALLET_KEY },
+        signing_hash,
+    };
+
+    fn ethers_hash(typed_data: Value) -> [u8; 32] {
+        serde_json::from_value::<EthersTypedData>(typed_data).unwrap().encode_eip712().unwrap()
+    }
+
+    fn market_action_typed_data(action: &MarketAction) -> Value {
+        typed_data::<MarketAction>(
+            &Eip712Domain::new(1),
+            json!({
+                "actor": action.actor,
+                "table": action.table.name(),
+                "action": action.action,
+                "content": format!("0x{}", ethers::utils::hex::encode(action.content)),
+                "nonce": action.nonce,
+                "deadline": action.deadline
+            })
+        )
+    }
+
+    #[test]
+    fn login_hash_matches_ethers() {
+        let message = SiweMessage { not_before: Some(NOW), ..siwe_message() };
+        let hash = signing_hash(&Eip712Domain::new(1), &Login::from(&message)).unwrap();
+        assert_eq!(hash, ethers_hash(login_typed_data(&message, 1)));
+
+        // a message without statement or times hashes them as empty and 0
+        let mut message = message;
+        message.statement = None;
+        message.expiration_time = None;
+        message.not_before = None;
+        let hash = signing_hash(&Eip712Domain::new(1), &Login::from(&message)).unwrap();
+        assert_eq!(hash, ethers_hash(login_typed_data(&message, 1)));
+    }
+
+    #[test]
+    fn market_action_hash_matches_ethers() {
+        let action = market_action();
+        let hash = signing_hash(&Eip712Domain::new(1), &action).unwrap();
+        assert_eq!(hash, ethers_hash(market_action_typed_data(&action)));
+    }
+
+    #[test]
+    fn wallet_signatures_recover_the_wallet() {
+        let wallet: LocalWallet = WALLET_KEY.parse().unwrap();
+        let address = format!("{:?}", wallet.address());
+        let domain = Eip712Domain::new(1);
+
+        let login = Login::from(&siwe_message());
+        let signature = wallet.sign_hash(H256::from(signing_hash(&domain, &login).unwrap())).unwrap();
+        let signer = recover_typed_signer(&domain, &login, &signature.to_string()).unwrap();
+        assert_eq!(address_hex(&signer), address);
+
+        let action = market_action();
+        let signature = wallet.sign_hash(H256::from(signing_hash(&domain, &action).unwrap())).unwrap();
+        let signer = recover_typed_signer(&domain, &action, &signature.to_string()).unwrap();
+        assert_eq!(address_hex(&signer), address);
+
+        // the nonce is signed, another one does not recover the wallet
+        let mut replayed = action;
+        replayed.nonce = "nonce87654321".to_string();
+        let signer = recover_typed_signer(&domain, &replayed, &signature.to_string()).unwrap();
+        assert_ne!(address_hex(&signer), address);
+    }
+}
@@ -0,0 +1,121 @@
+use axum::{ http::StatusCode, response::{ IntoResponse, Response }, Json };
+use sea_orm::DbErr;
+use serde_json::json;
+use tokio::task::JoinError;
+
+// Every way a request can fail. Handlers return it, and it answers with its status and a
+// `{status, code, message}` body, so a failed request never takes the server down.
+#[derive(Debug)]
+pub enum ApiError {
+    Db(DbErr),
+    Redis(redis::RedisError),
+    // proving or executing a guest failed
+    Prover(String),
+    // a receipt, a stored proof or the block chain does not verify
+    Verification(String),
+    // the request itself is malformed
+    Validation(String),
+    Unauthorized(String),
+    NotFound(String),
+    // the request does not apply to the current state, such as a stale parent block
+    Conflict(String),
+    Timeout(String),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Db(DbErr::RecordNotFound(_)) => StatusCode::NOT_FOUND,
+            ApiError::Db(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Redis(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Prover(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Verification(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Stable name of the failure, for clients to match on instead of the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Db(DbErr::RecordNotFound(_)) => "not_found",
+            ApiError::Db(_) => "database_error",
+            ApiError::Redis(_) => "redis_error",
+            ApiError::Prover(_) => "prover_error",
+            ApiError::Verification(_) => "verification_failed",
+            ApiError::Validation(_) => "invalid_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Timeout(_) => "timeout",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::Db(e) => e.to_string(),
+            ApiError::Redis(e) => e.to_string(),
+            ApiError::Prover(message) |
+            ApiError::Verification(message) |
+            ApiError::Validation(message) |
+            ApiError::Unauthorized(message) |
+            ApiError::NotFound(message) |
+            ApiError::Conflict(message) |
+            ApiError::Timeout(message) |
+            ApiError::Internal(message) => message.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        // client errors are answered, server errors are also worth a line in the log
+        if status.is_server_error() {
+            tracing::error!(%status, "Request failed: {}", self);
+        }
+        (
+            status,
+            Json(
+                json!({
+            "status": "error",
+            "code": self.code(),
+            "message": self.message()
+        })
+            ),
+        ).into_response()
+    }
+}
+
+impl From<DbErr> for ApiError {
+    fn from(e: DbErr) -> Self {
+        ApiError::Db(e)
+    }
+}
+
+impl From<redis::RedisError> for ApiError {
+    fn from(e: redis::RedisError) -> Self {
+        ApiError::Redis(e)
+    }
+}
+
+// A proving task that panicked or was cancelled.
+impl From<JoinError> for ApiError {
+    fn from(e: JoinError) -> Self {
+        ApiError::Internal(e.to_string())
+    }
+}
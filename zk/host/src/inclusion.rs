@@ -0,0 +1,104 @@
+use axum::Json;
+use oil_tokenization_core::{ slot, slot_leaves, InclusionProof, MerkleTree, StateTable };
+use sea_orm::DatabaseConnection;
+use serde_json::{ Value, json };
+use std::sync::Arc;
+
+use crate::{
+    block::head_roots,
+    error::ApiError,
+    overall::{
+        get_all_comments,
+        get_all_oil_tokens,
+        get_all_tokenizations,
+        get_comment_leaves,
+        get_oil_token_leaves,
+        get_tokenization_leaves,
+    },
+};
+
+// Builds the inclusion proof of the record `id` in `table` against the roots committed by the
+// head block, so it checks against that block's receipt. Only the table of the record is
+// loaded, its rows must still be those the head block committed.
+pub async fn build_inclusion_proof(
+    db: &DatabaseConnection,
+    table: StateTable,
+    id: i32
+) -> Result<InclusionProof, ApiError> {
+    let (block, roots) = head_roots(db).await
+        .map_err(ApiError::Internal)?
+        .ok_or_else(|| ApiError::Conflict("No block proven yet".to_string()))?;
+
+    // every row sits at the slot of its id, as every block commits it
+    let rows = match table {
+        StateTable::OilToken => get_oil_token_leaves(&get_all_oil_tokens(db).await?),
+        StateTable::Tokenization => get_tokenization_leaves(&get_all_tokenizations(db).await?),
+        StateTable::Comment => get_comment_leaves(&get_all_comments(db).await?),
+    };
+    let leaves = slot_leaves(&rows).map_err(ApiError::Internal)?;
+
+    let tree = MerkleTree::new(&leaves);
+    if tree.root() != roots.table(table).root {
+        return Err(
+            ApiError::Conflict(
+                format!(
+                    "The {:?} table has changes not proven by block {}, sync it first",
+                    table,
+                    block.height
+                )
+            )
+        );
+    }
+    let index = slot(id)
+        .map(|index| index as usize)
+        .filter(|index| leaves.get(*index).is_some_and(|leaf| !leaf.is_empty()))
+        .ok_or_else(|| ApiError::NotFound("Record not found".to_string()))?;
+    let proof = tree
+        .proof(index)
+        .ok_or_else(|| ApiError::Internal("Failed to build proof".to_string()))?;
+
+    Ok(InclusionProof {
+        table,
+        leaf: leaves[index].clone(),
+        proof,
+        sub_state_root: tree.root(),
+        overall_root: block.root,
+        height: block.height,
+    })
+}
+
+async fn inclusion_proof_response(
+    db: &DatabaseConnection,
+    table: StateTable,
+    id: i32
+) -> Result<Json<Value>, ApiError> {
+    let inclusion_proof = build_inclusion_proof(db, table, id).await?;
+    Ok(Json(json!({
+        "status": "success",
+        "data": inclusion_proof
+    })))
+}
+
+// Handler to get the merkle proof of an oil token
+pub async fn get_oil_token_proof(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    inclusion_proof_response(&db, StateTable::OilToken, id).await
+}
+
+// Handler to get the merkle proof of a tokenization
+pub async fn get_tokenization_proof(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    inclusion_proof_response(&db, StateTable::Tokenization, id).await
+}
+
+// Handler to get the merkle proof of a comment
+pub async fn get_comment_proof(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    inclusion_proof_response(&db, StateTable::Comment, id).await
+}
@@ -0,0 +1,681 @@
+use axum::Json;
+use chrono::{ NaiveDateTime, TimeDelta, Utc };
+use dotenv::dotenv;
+use entity::proof_job;
+use oil_tokenization_core::{ slot_leaves, VerifyParams };
+use risc0_zkvm::Receipt;
+use sea_orm::{
+    ActiveModelTrait,
+    ColumnTrait,
+    ConnectionTrait,
+    DatabaseConnection,
+    DbBackend,
+    DbErr,
+    EntityTrait,
+    QueryFilter,
+    Statement,
+    TransactionTrait,
+};
+use serde::Deserialize;
+use serde_json::{ Value, json };
+use std::env;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    auth::prove_verify,
+    comment::init_comment,
+    delta::{ commit_synced, sync_delta, SyncOutcome, TableLeaves },
+    error::ApiError,
+    keyed::{ commit_keyed, load_live_leaves },
+    oil_token::init_oil_token,
+    overall::{
+        get_all_comments,
+        get_all_oil_tokens,
+        get_all_tokenizations,
+        get_comment_leaves,
+        get_oil_token_leaves,
+        get_tokenization_leaves,
+    },
+    prover::{ CompressRequest, Guest, ReceiptKind, SharedProver },
+    sync_state,
+    tokenization::init_tokenization,
+    transition::{ prove_queued, TransitionJob },
+    SessionStats,
+};
+
+// How long an idle worker waits before looking for a queued job again.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// How long a running job stays leased to its worker without a heartbeat.
+const LEASE_SECONDS: i64 = 60;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+// Advisory lock every claim takes, so two workers never both see no transition running.
+const CLAIM_LOCK: i64 = 0x006f_696c_5f6a_6f62;
+
+// Proof a job runs, which decides how its input is read and its receipt produced.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JobKind {
+    InitOilToken,
+    InitTokenization,
+    InitComment,
+    InitOverall,
+    InitKeyed,
+    Sync,
+    SyncDelta,
+    Verify,
+    Transition,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::InitOilToken => "init_oil_token",
+            JobKind::InitTokenization => "init_tokenization",
+            JobKind::InitComment => "init_comment",
+            JobKind::InitOverall => "init_overall",
+            JobKind::InitKeyed => "init_keyed",
+            JobKind::Sync => "sync",
+            JobKind::SyncDelta => "sync_delta",
+            JobKind::Verify => "verify",
+            JobKind::Transition => "transition",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Result<Self, String> {
+        match kind {
+            "init_oil_token" => Ok(JobKind::InitOilToken),
+            "init_tokenization" => Ok(JobKind::InitTokenization),
+            "init_comment" => Ok(JobKind::InitComment),
+            "init_overall" => Ok(JobKind::InitOverall),
+            "init_keyed" => Ok(JobKind::InitKeyed),
+            "sync" => Ok(JobKind::Sync),
+            "sync_delta" => Ok(JobKind::SyncDelta),
+            "verify" => Ok(JobKind::Verify),
+            "transition" => Ok(JobKind::Transition),
+            _ => Err(format!("Unknown job kind {}", kind)),
+        }
+    }
+
+    // Guest whose receipt the job produces. A delta sync falls back to a full proof when
+    // the chain head has no synced leaves, its result tells which one it produced.
+    pub fn guest(&self, result: Option<&Value>) -> Guest {
+        match self {
+            JobKind::InitOilToken | JobKind::InitTokenization | JobKind::InitComment => {
+                Guest::InitState
+            }
+            JobKind::InitOverall | JobKind::Sync => Guest::InitOverall,
+            JobKind::SyncDelta => {
+                if result.is_some_and(|result| result["mode"] == "full") {
+                    Guest::InitOverall
+                } else {
+                    Guest::SyncDelta
+                }
+            }
+            JobKind::InitKeyed => Guest::InitKeyed,
+            JobKind::Verify => Guest::Verify,
+            JobKind::Transition => Guest::Transition,
+        }
+    }
+}
+
+// Stage a job is queued for. A proven receipt that is not of the kind the job asked for
+// is compressed in a stage of its own, with attempts of its own.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JobStage {
+    Prove,
+    Compress,
+}
+
+impl JobStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStage::Prove => "prove",
+            JobStage::Compress => "compress",
+        }
+    }
+
+    pub fn parse(stage: &str) -> Result<Self, String> {
+        match stage {
+            "prove" => Ok(JobStage::Prove),
+            "compress" => Ok(JobStage::Compress),
+            _ => Err(format!("Unknown job stage {}", stage)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+// What a finished job stores: its receipt and stats, plus any kind specific result.
+struct JobOutput {
+    receipt: Receipt,
+    stats: SessionStats,
+    result: Option<Value>,
+}
+
+// Why a job did not finish. Transient failures are retried, permanent ones would fail
+// the same way on every attempt.
+enum JobError {
+    Transient(String),
+    Permanent(String),
+}
+
+impl From<String> for JobError {
+    fn from(e: String) -> Self {
+        JobError::Transient(e)
+    }
+}
+
+// Receipt kind asked for with `?receipt_kind=`, composite by default.
+#[derive(Deserialize)]
+pub struct ReceiptQuery {
+    #[serde(default)]
+    pub receipt_kind: ReceiptKind,
+}
+
+// Queues a proof of `kind` and returns its id. Init and sync jobs read the tables when
+// they run, verify and transition jobs prove the params given as `input`. The receipt
+// ends up as `receipt_kind`, or as a smaller kind when the guest needs one.
+pub async fn enqueue_job(
+    db: &DatabaseConnection,
+    kind: JobKind,
+    input: Option<Value>,
+    receipt_kind: ReceiptKind
+) -> Result<i32, DbErr> {
+    use sea_orm::ActiveValue::{ NotSet, Set };
+    let now = Utc::now().naive_utc();
+    let job = proof_job::ActiveModel {
+        id: NotSet,
+        kind: Set(kind.as_str().to_string()),
+        status: Set(JobStatus::Queued.as_str().to_string()),
+        input: Set(input),
+        receipt: Set(None),
+        stats: Set(None),
+        result: Set(None),
+        error: Set(None),
+        attempts: Set(0),
+        locked_by: Set(None),
+        lease_expires_at: Set(None),
+        heartbeat_at: Set(None),
+        receipt_kind: Set(receipt_kind.as_str().to_string()),
+        stage: Set(JobStage::Prove.as_str().to_string()),
+        created_at: Set(now),
+        updated_at: Set(now),
+    };
+    Ok(job.insert(db).await?.id)
+}
+
+// Handler response for a queued job, polled at /api/jobs/{id}.
+pub async fn enqueue_response(
+    db: &DatabaseConnection,
+    kind: JobKind,
+    input: Option<Value>,
+    receipt_kind: ReceiptKind
+) -> Result<Json<Value>, ApiError> {
+    let id = enqueue_job(db, kind, input, receipt_kind).await?;
+    Ok(Json(json!({
+        "status": "success",
+        "job_id": id,
+        "job_status": JobStatus::Queued.as_str()
+    })))
+}
+
+fn lease_expiry(now: NaiveDateTime) -> NaiveDateTime {
+    now + TimeDelta::seconds(LEASE_SECONDS)
+}
+
+// Leases the oldest queued job to `worker`, or a running job whose worker stopped
+// renewing its lease. SKIP LOCKED lets several workers claim jobs at once without
+// ever handing the same job out twice. Transition jobs are handed out one at a time,
+// each is staged on the block the one before stored.
+async fn claim_job(
+    db: &DatabaseConnection,
+    worker: &str
+) -> Result<Option<proof_job::Model>, DbErr> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+    txn.execute(
+        Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT pg_advisory_xact_lock($1)",
+            [CLAIM_LOCK.into()]
+        )
+    ).await?;
+    let job = proof_job::Entity
+        ::find()
+        .from_raw_sql(
+            Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                r#"UPDATE proof_job
+                SET status = $1, locked_by = $2, lease_expires_at = $3, heartbeat_at = $4,
+                    attempts = attempts + 1, updated_at = $4
+                WHERE id = (
+                    SELECT id FROM proof_job
+                    WHERE (status = $5 OR (status = $1 AND lease_expires_at < $4))
+                    AND NOT (kind = $6 AND EXISTS (
+                        SELECT 1 FROM proof_job AS running
+                        WHERE running.kind = $6 AND running.status = $1
+                        AND running.lease_expires_at >= $4
+                    ))
+                    ORDER BY id LIMIT 1 FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *"#,
+                [
+                    JobStatus::Running.as_str().into(),
+                    worker.into(),
+                    lease_expiry(now).into(),
+                    now.into(),
+                    JobStatus::Queued.as_str().into(),
+                    JobKind::Transition.as_str().into(),
+                ]
+            )
+        )
+        .one(&txn).await?;
+    txn.commit().await?;
+    Ok(job)
+}
+
+// Renews the lease of a running job until the worker is done with it.
+async fn heartbeat(db: Arc<DatabaseConnection>, id: i32, worker: String) {
+    use sea_orm::ActiveValue::Set;
+    loop {
+        tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+        let now = Utc::now().naive_utc();
+        let renewed = proof_job::Entity
+            ::update_many()
+            .set(proof_job::ActiveModel {
+                heartbeat_at: Set(Some(now)),
+                lease_expires_at: Set(Some(lease_expiry(now))),
+                ..Default::default()
+            })
+            .filter(proof_job::Column::Id.eq(id))
+            .filter(proof_job::Column::LockedBy.eq(worker.as_str()))
+            .filter(proof_job::Column::Status.eq(JobStatus::Running.as_str()))
+            .exec(&*db).await;
+        match renewed {
+            Ok(result) if result.rows_affected == 0 => {
+                tracing::warn!(job = id, %worker, "Lost the lease of proof job");
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!(job = id, "Failed to renew the lease of proof job: {}", e),
+        }
+    }
+}
+
+// Runs a blocking prover call off the async runtime.
+async fn prove_blocking<T: Send + 'static>(
+    prove: impl FnOnce() -> Result<T, String> + Send + 'static
+) -> Result<T, String> {
+    tokio::task::spawn_blocking(prove).await.map_err(|e| e.to_string())?
+}
+
+fn job_input<T: serde::de::DeserializeOwned>(input: Option<Value>) -> Result<T, JobError> {
+    let input = input.ok_or_else(|| JobError::Permanent("Job has no input".to_string()))?;
+    serde_json::from_value(input).map_err(|e| JobError::Permanent(e.to_string()))
+}
+
+async fn run_job(
+    db: &DatabaseConnection,
+    prover: SharedProver,
+    kind: JobKind,
+    input: Option<Value>
+) -> Result<JobOutput, JobError> {
+    match kind {
+        JobKind::InitOilToken => {
+            let oil_tokens = get_all_oil_tokens(db).await.map_err(|e| e.to_string())?;
+            let leaves = slot_leaves(&get_oil_token_leaves(&oil_tokens))?;
+            let commit = prove_blocking(move || init_oil_token(&*prover, leaves)).await?;
+            Ok(JobOutput { receipt: commit.receipt, stats: commit.stats, result: None })
+        }
+        JobKind::InitTokenization => {
+            let tokenizations = get_all_tokenizations(db).await.map_err(|e| e.to_string())?;
+            let leaves = slot_leaves(&get_tokenization_leaves(&tokenizations))?;
+            let commit = prove_blocking(move || init_tokenization(&*prover, leaves)).await?;
+            Ok(JobOutput { receipt: commit.receipt, stats: commit.stats, result: None })
+        }
+        JobKind::InitComment => {
+            let comments = get_all_comments(db).await.map_err(|e| e.to_string())?;
+            let leaves = slot_leaves(&get_comment_leaves(&comments))?;
+            let commit = prove_blocking(move || init_comment(&*prover, leaves)).await?;
+            Ok(JobOutput { receipt: commit.receipt, stats: commit.stats, result: None })
+        }
+        JobKind::InitOverall => {
+            let leaves = TableLeaves::load(db).await.map_err(|e| e.to_string())?;
+            let commit = commit_synced(db, prover, &leaves).await.map_err(|e| e.message())?;
+            Ok(JobOutput { receipt: commit.receipt, stats: commit.stats, result: None })
+        }
+        JobKind::InitKeyed => {
+            let leaves = load_live_leaves(db).await.map_err(|e| e.to_string())?;
+            let commit = commit_keyed(db, prover, leaves).await.map_err(|e| e.message())?;
+            Ok(JobOutput { receipt: commit.receipt, stats: commit.stats, result: None })
+        }
+        JobKind::Sync => {
+            let leaves = TableLeaves::load(db).await.map_err(|e| e.to_string())?;
+            let commit = commit_synced(db, prover, &leaves).await.map_err(|e| e.message())?;
+            let hash = sync_state::sync_state(&commit).await.map_err(|e| e.to_string())?;
+            Ok(JobOutput {
+                receipt: commit.receipt,
+                stats: commit.stats,
+                result: Some(json!({ "hash": hash })),
+            })
+        }
+        JobKind::SyncDelta => {
+            let outcome = sync_delta(db, prover).await.map_err(|e| match e {
+                ApiError::Conflict(e) => JobError::Permanent(e),
+                e => JobError::Transient(e.message()),
+            })?;
+            Ok(match outcome {
+                SyncOutcome::Full(commit) =>
+                    JobOutput {
+                        receipt: commit.receipt,
+                        stats: commit.stats,
+                        result: Some(json!({ "mode": "full", "updates": null })),
+                    },
+                SyncOutcome::Delta { commit, updates } =>
+                    JobOutput {
+                        receipt: commit.receipt,
+                        stats: commit.stats,
+                        result: Some(json!({ "mode": "delta", "updates": updates })),
+                    },
+            })
+        }
+        JobKind::Verify => {
+            let params: VerifyParams = job_input(input)?;
+            let commit = prove_blocking(move || prove_verify(&*prover, &params)).await?;
+            // the guest recovers the signer, a forged signature commits verified: false
+            if !commit.get_commit()?.verified {
+                return Err(JobError::Permanent("Signature verification failed".to_string()));
+            }
+            Ok(JobOutput { receipt: commit.receipt, stats: commit.stats, result: None })
+        }
+        JobKind::Transition => {
+            let job: TransitionJob = job_input(input)?;
+            // staged again on the head the worker finds, a block another write got onto
+            // the same parent first sends the job back to the queue to be staged anew
+            let (commit, block) = prove_queued(db, prover, job).await.map_err(|e| match e {
+                ApiError::Validation(e) => JobError::Permanent(e),
+                e => JobError::Transient(e.message()),
+            })?;
+            Ok(JobOutput {
+                receipt: commit.receipt,
+                stats: commit.stats,
+                result: Some(json!({ "height": block.height, "root": block.root })),
+            })
+        }
+    }
+}
+
+// Compresses the receipt a job proved into the kind it asked for.
+async fn compress_job(prover: SharedProver, job: &proof_job::Model) -> Result<JobOutput, JobError> {
+    let kind = JobKind::parse(&job.kind).map_err(JobError::Permanent)?;
+    let receipt_kind = ReceiptKind::parse(&job.receipt_kind).map_err(JobError::Permanent)?;
+    let (receipt, stats) = job.receipt
+        .clone()
+        .zip(job.stats.clone())
+        .ok_or_else(|| JobError::Permanent("Job has no receipt to compress".to_string()))?;
+    let request = CompressRequest {
+        guest: kind.guest(job.result.as_ref()),
+        receipt: serde_json::from_value(receipt).map_err(|e| JobError::Permanent(e.to_string()))?,
+        stats: serde_json::from_value(stats).map_err(|e| JobError::Permanent(e.to_string()))?,
+        receipt_kind,
+    };
+    let proof = prove_blocking(move || prover.compress(request)).await?;
+    Ok(JobOutput { receipt: proof.receipt, stats: proof.stats, result: job.result.clone() })
+}
+
+// Whether a receipt the job proved still has to be compressed. Fake receipts have no
+// kind and are never compressed.
+fn needs_compression(job: &proof_job::Model, receipt: &Receipt) -> bool {
+    let Ok(requested) = ReceiptKind::parse(&job.receipt_kind) else {
+        return false;
+    };
+    job.stage == JobStage::Prove.as_str() &&
+        ReceiptKind::of(receipt).is_some_and(|kind| kind < requested)
+}
+
+// Stores the outcome of a job, unless another worker took it over meanwhile. A transient
+// failure queues the job again until it has had `max_attempts` attempts.
+async fn finish_job(
+    db: &DatabaseConnection,
+    job: &proof_job::Model,
+    worker: &str,
+    output: Result<JobOutput, JobError>,
+    max_attempts: i32
+) -> Result<(), DbErr> {
+    use sea_orm::ActiveValue::Set;
+    let mut update = proof_job::ActiveModel {
+        locked_by: Set(None),
+        lease_expires_at: Set(None),
+        updated_at: Set(Utc::now().naive_utc()),
+        ..Default::default()
+    };
+    match output {
+        Ok(output) if needs_compression(job, &output.receipt) => {
+            update.status = Set(JobStatus::Queued.as_str().to_string());
+            update.stage = Set(JobStage::Compress.as_str().to_string());
+            update.attempts = Set(0);
+            update.receipt = Set(Some(json!(output.receipt)));
+            update.stats = Set(Some(json!(output.stats)));
+            update.result = Set(output.result);
+            update.error = Set(None);
+        }
+        Ok(output) => {
+            update.status = Set(JobStatus::Done.as_str().to_string());
+            update.receipt = Set(Some(json!(output.receipt)));
+            update.stats = Set(Some(json!(output.stats)));
+            update.result = Set(output.result);
+            update.error = Set(None);
+        }
+        Err(JobError::Transient(e)) if job.attempts < max_attempts => {
+            update.status = Set(JobStatus::Queued.as_str().to_string());
+            update.error = Set(Some(e));
+        }
+        Err(JobError::Transient(e) | JobError::Permanent(e)) => {
+            tracing::warn!(job = job.id, kind = %job.kind, "Proof job failed: {}", e);
+            update.status = Set(JobStatus::Failed.as_str().to_string());
+            update.error = Set(Some(e));
+        }
+    }
+    let result = proof_job::Entity
+        ::update_many()
+        .set(update)
+        .filter(proof_job::Column::Id.eq(job.id))
+        .filter(proof_job::Column::LockedBy.eq(worker))
+        .exec(db).await?;
+    if result.rows_affected == 0 {
+        tracing::warn!(job = job.id, %worker, "Proof job was taken over, dropping the result");
+    }
+    Ok(())
+}
+
+async fn work(
+    db: Arc<DatabaseConnection>,
+    prover: SharedProver,
+    worker: String,
+    max_attempts: i32
+) {
+    loop {
+        let job = match claim_job(&db, &worker).await {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            }
+            Err(e) => {
+                tracing::error!("Failed to claim a proof job: {}", e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            }
+        };
+
+        // a job whose worker died on every attempt is not handed out again
+        let output = if job.attempts > max_attempts {
+            let last_error = job.error.clone().unwrap_or_default();
+            Err(JobError::Permanent(format!("Gave up after {} attempts: {}", max_attempts, last_error)))
+        } else {
+            let renew = tokio::spawn(heartbeat(db.clone(), job.id, worker.clone()));
+            let output = match (JobStage::parse(&job.stage), JobKind::parse(&job.kind)) {
+                (Ok(JobStage::Prove), Ok(kind)) => {
+                    run_job(&db, prover.clone(), kind, job.input.clone()).await
+                }
+                (Ok(JobStage::Compress), Ok(_)) => compress_job(prover.clone(), &job).await,
+                (Err(e), _) | (_, Err(e)) => Err(JobError::Permanent(e)),
+            };
+            renew.abort();
+            output
+        };
+        if let Err(e) = finish_job(&db, &job, &worker, output, max_attempts).await {
+            tracing::error!(job = job.id, "Failed to store proof job: {}", e);
+        }
+    }
+}
+
+// Number of jobs proven at once by this process, from PROVER_WORKERS. Each proof can take
+// most of the machine, so the default is one. API servers that leave proving to
+// prover-worker processes set it to 0.
+pub fn worker_count() -> usize {
+    dotenv().ok();
+    env::var("PROVER_WORKERS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(1)
+}
+
+// Attempts a job gets before it fails, from PROVER_MAX_ATTEMPTS.
+pub fn max_attempts() -> i32 {
+    dotenv().ok();
+    env::var("PROVER_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|attempts| attempts.parse().ok())
+        .filter(|attempts| *attempts > 0)
+        .unwrap_or(3)
+}
+
+pub fn spawn_workers(db: Arc<DatabaseConnection>, prover: SharedProver, count: usize) {
+    let host = env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string());
+    let max_attempts = max_attempts();
+    for index in 0..count {
+        let worker = format!("{}-{}-{}", host, process::id(), index);
+        tokio::spawn(work(db.clone(), prover.clone(), worker, max_attempts));
+    }
+}
+
+// Handler to get the status of a proof job, with its receipt and stats once it is done
+pub async fn get_job_handler(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    let job = proof_job::Entity
+        ::find_by_id(id)
+        .one(&*db).await?
+        .ok_or_else(|| ApiError::NotFound("Job not found".to_string()))?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": job
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prover::{ Proof, ProveRequest, ProverBackend };
+    use oil_tokenization_core::{ SignatureScheme, VerifyCommit };
+    use risc0_zkvm::{ sha::Digest, FakeReceipt, InnerReceipt, ReceiptClaim };
+
+    // Backend answering every proof with a fake receipt of `commit`, or with its error.
+    struct StubProver {
+        commit: Result<VerifyCommit, String>,
+    }
+
+    impl ProverBackend for StubProver {
+        fn prove(&self, request: &ProveRequest) -> Result<Proof, String> {
+            let commit = self.commit.clone()?;
+            let journal: Vec<u8> = risc0_zkvm::serde
+                ::to_vec(&commit)
+                .map_err(|e| e.to_string())?
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .collect();
+            let claim = ReceiptClaim::ok(Digest::from(request.guest.image_id()), journal.clone());
+            let stats = SessionStats {
+                segments: 1,
+                total_cycles: 0,
+                user_cycles: 0,
+                paging_cycles: 0,
+                reserved_cycles: 0,
+            };
+            Ok(Proof { receipt: Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal), stats })
+        }
+
+        fn compress(&self, request: CompressRequest) -> Result<Proof, String> {
+            Ok(Proof { receipt: request.receipt, stats: request.stats })
+        }
+    }
+
+    fn verify_commit(verified: bool) -> VerifyCommit {
+        VerifyCommit {
+            verified,
+            address: "0x01".to_string(),
+            timestamp: 0,
+            username: "user".to_string(),
+            scheme: SignatureScheme::Eip191,
+            message: None,
+        }
+    }
+
+    // a verify job reads nothing from the database
+    async fn run_verify(commit: Result<VerifyCommit, String>) -> Result<JobOutput, JobError> {
+        let params = VerifyParams {
+            message: "message".to_string(),
+            signature_bytes: "0x".to_string(),
+            scheme: SignatureScheme::Eip191,
+            expected_addr: "0x01".to_string(),
+            domain: "localhost".to_string(),
+            chain_id: 1,
+            timestamp: 0,
+            username: "user".to_string(),
+        };
+        let prover: SharedProver = Arc::new(StubProver { commit });
+        let db = DatabaseConnection::default();
+        run_job(&db, prover, JobKind::Verify, Some(json!(params))).await
+    }
+
+    #[tokio::test]
+    async fn verify_job_stores_the_receipt_of_a_verified_login() {
+        let output = run_verify(Ok(verify_commit(true))).await.ok().unwrap();
+        let commit: VerifyCommit = output.receipt.journal.decode().unwrap();
+        assert!(commit.verified);
+        assert_eq!(output.stats.segments, 1);
+    }
+
+    #[tokio::test]
+    async fn verify_job_fails_for_good_on_a_forged_signature_and_retries_a_prover_error() {
+        assert!(matches!(run_verify(Ok(verify_commit(false))).await, Err(JobError::Permanent(_))));
+        let unreachable = Err("Remote prover timed out".to_string());
+        assert!(matches!(run_verify(unreachable).await, Err(JobError::Transient(_))));
+        // input the guest cannot read fails on every attempt
+        let prover: SharedProver = Arc::new(StubProver { commit: Ok(verify_commit(true)) });
+        let db = DatabaseConnection::default();
+        let output = run_job(&db, prover, JobKind::Verify, None).await;
+        assert!(matches!(output, Err(JobError::Permanent(_))));
+    }
+}
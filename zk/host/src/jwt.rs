@@ -1,6 +1,8 @@
 use jsonwebtoken::{ encode, Header, EncodingKey };
 use serde::{ Serialize, Deserialize };
 
+use crate::error::ApiError;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub addr: String, // Ethereum address
@@ -8,12 +10,8 @@ pub struct Claims {
     pub username: String,
 }
 
-pub fn issue_token(address: &str, username: &str) -> String {
-    let expiration = chrono::Utc
-        ::now()
-        .checked_add_signed(chrono::Duration::minutes(15))
-        .unwrap()
-        .timestamp() as usize;
+pub fn issue_token(address: &str, username: &str) -> Result<String, ApiError> {
+    let expiration = (chrono::Utc::now() + chrono::Duration::minutes(15)).timestamp() as usize;
 
     let claims = Claims {
         addr: address.to_string(),
@@ -22,5 +20,5 @@ pub fn issue_token(address: &str, username: &str) -> String {
     };
 
     let key = EncodingKey::from_secret("your-secret".as_ref());
-    encode(&Header::default(), &claims, &key).unwrap()
+    encode(&Header::default(), &claims, &key).map_err(|e| ApiError::Internal(e.to_string()))
 }
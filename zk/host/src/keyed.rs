@@ -0,0 +1,217 @@
+use axum::Json;
+use chrono::Utc;
+use entity::{ keyed_state, TokenStatus };
+use oil_tokenization_core::{ KeyedParams, KeyedProof, KeyedState, SparseMerkleTree, StateTable };
+use methods::INIT_KEYED_ID;
+use risc0_zkvm::Receipt;
+use sea_orm::{
+    ActiveModelTrait,
+    ColumnTrait,
+    DatabaseConnection,
+    EntityTrait,
+    QueryFilter,
+    QueryOrder,
+    TransactionTrait,
+};
+use serde::{ Deserialize, Serialize };
+use serde_json::{ Value, json };
+use std::sync::Arc;
+
+use crate::{
+    delta::TableLeaves,
+    error::ApiError,
+    job::{ enqueue_response, JobKind, ReceiptQuery },
+    prover::{ verify_receipt, Guest, ProveRequest, ProverBackend, SharedProver },
+    overall::{
+        get_all_comments,
+        get_all_oil_tokens,
+        get_all_tokenizations,
+        get_comment_leaves,
+        get_oil_token_leaves,
+        get_tokenization_leaves,
+    },
+    SessionStats,
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct KeyedCommit {
+    pub receipt: Receipt,
+    pub stats: SessionStats,
+}
+
+impl KeyedCommit {
+    pub fn get_commit(&self) -> Result<KeyedState, String> {
+        let state = self.receipt.journal.decode().map_err(|e| e.to_string())?;
+        Ok(state)
+    }
+
+    pub fn verify_and_get_commit(&self) -> Result<KeyedState, String> {
+        verify_receipt(&self.receipt, INIT_KEYED_ID)?;
+        self.get_commit()
+    }
+}
+
+// Rows of the live state keyed by primary key. Cancelled lots are left out, so a
+// non-membership proof shows a cancelled lot is no longer live.
+pub async fn load_live_leaves(db: &DatabaseConnection) -> Result<TableLeaves, sea_orm::DbErr> {
+    let oil_tokens: Vec<_> = get_all_oil_tokens(db)
+        .await?
+        .into_iter()
+        .filter(|o| o.status != Some(TokenStatus::Cancelled))
+        .collect();
+    let tokenizations = get_all_tokenizations(db).await?;
+    let comments = get_all_comments(db).await?;
+    Ok(TableLeaves {
+        oil_token: get_oil_token_leaves(&oil_tokens),
+        tokenization: get_tokenization_leaves(&tokenizations),
+        comment: get_comment_leaves(&comments),
+    })
+}
+
+fn keyed_params(leaves: TableLeaves) -> KeyedParams {
+    KeyedParams {
+        oil_token_leaves: leaves.oil_token,
+        tokenization_leaves: leaves.tokenization,
+        comment_leaves: leaves.comment,
+    }
+}
+
+pub fn init_keyed(prover: &dyn ProverBackend, leaves: TableLeaves) -> Result<KeyedCommit, String> {
+    let params = keyed_params(leaves);
+    let proof = prover.prove(&ProveRequest::new(Guest::InitKeyed, &params)?)?;
+    let keyed_commit = KeyedCommit {
+        receipt: proof.receipt,
+        stats: proof.stats,
+    };
+    Ok(keyed_commit)
+}
+
+// Handler to queue the proof of the keyed state of the three tables, see /api/jobs/{id}
+pub async fn init_keyed_handler(
+    axum::extract::Query(query): axum::extract::Query<ReceiptQuery>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    enqueue_response(&db, JobKind::InitKeyed, None, query.receipt_kind).await
+}
+
+// Proves the keyed state of `leaves` and stores the leaves with its receipt, keyed proofs
+// are built from them. Only those of the latest keyed state proof are kept.
+pub async fn commit_keyed(
+    db: &DatabaseConnection,
+    prover: SharedProver,
+    leaves: TableLeaves
+) -> Result<KeyedCommit, ApiError> {
+    use sea_orm::ActiveValue::{ NotSet, Set };
+    let stored = serde_json::to_value(&leaves).map_err(|e| ApiError::Internal(e.to_string()))?;
+    let keyed_commit = tokio::task
+        ::spawn_blocking(move || init_keyed(&*prover, leaves))
+        .await?
+        .map_err(ApiError::Prover)?;
+    let state = keyed_commit.get_commit().map_err(ApiError::Internal)?;
+    let row = keyed_state::ActiveModel {
+        id: NotSet,
+        root: Set(state.overall.to_string()),
+        leaves: Set(stored),
+        receipt: Set(json!(keyed_commit.receipt)),
+        created_at: Set(Utc::now().naive_utc()),
+    };
+
+    let txn = db.begin().await?;
+    let row = row.insert(&txn).await?;
+    keyed_state::Entity
+        ::delete_many()
+        .filter(keyed_state::Column::Id.lt(row.id))
+        .exec(&txn).await?;
+    txn.commit().await?;
+    Ok(keyed_commit)
+}
+
+// Leaves and receipt of the latest keyed state proof.
+async fn latest_keyed(db: &DatabaseConnection) -> Result<(TableLeaves, Receipt), ApiError> {
+    let row = keyed_state::Entity
+        ::find()
+        .order_by_desc(keyed_state::Column::Id)
+        .one(db).await?
+        .ok_or_else(|| {
+            ApiError::Conflict("No keyed state proven yet, see /api/db/init_keyed".to_string())
+        })?;
+    let leaves = serde_json::from_value(row.leaves).map_err(|e| ApiError::Internal(e.to_string()))?;
+    let receipt = serde_json::from_value(row.receipt).map_err(|e| ApiError::Internal(e.to_string()))?;
+    Ok((leaves, receipt))
+}
+
+// Builds the membership proof of the record `id`, or its non-membership proof when
+// the record was deleted or never existed, against the latest proven keyed state. Returns
+// the receipt of that state too, the proof checks against its journal.
+pub async fn build_keyed_proof(
+    db: &DatabaseConnection,
+    table: StateTable,
+    id: i32
+) -> Result<(KeyedProof, Receipt), ApiError> {
+    let (leaves, receipt) = latest_keyed(db).await?;
+
+    let rows = match table {
+        StateTable::OilToken => &leaves.oil_token,
+        StateTable::Tokenization => &leaves.tokenization,
+        StateTable::Comment => &leaves.comment,
+    };
+    let leaf = rows
+        .iter()
+        .find(|(row_id, _)| *row_id == id)
+        .map(|(_, leaf)| leaf.clone());
+    let proof = SparseMerkleTree::new(table, rows).map_err(ApiError::Internal)?.proof(id);
+
+    let keyed_state = KeyedState::init(&keyed_params(leaves)).map_err(ApiError::Internal)?;
+    let committed: KeyedState = receipt.journal.decode().map_err(|e| ApiError::Internal(e.to_string()))?;
+    if keyed_state.overall != committed.overall {
+        return Err(ApiError::Internal("Stored keyed leaves do not match their receipt".to_string()));
+    }
+
+    let keyed_proof = KeyedProof {
+        table,
+        id,
+        leaf,
+        proof,
+        sub_state_root: keyed_state.sub_state_root(table),
+        overall_root: keyed_state.overall,
+    };
+    Ok((keyed_proof, receipt))
+}
+
+async fn keyed_proof_response(
+    db: &DatabaseConnection,
+    table: StateTable,
+    id: i32
+) -> Result<Json<Value>, ApiError> {
+    let (keyed_proof, receipt) = build_keyed_proof(db, table, id).await?;
+    Ok(Json(json!({
+        "status": "success",
+        "member": keyed_proof.leaf.is_some(),
+        "data": keyed_proof,
+        "receipt": receipt
+    })))
+}
+
+// Handler to get the keyed merkle proof of an oil token
+pub async fn get_oil_token_keyed_proof(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    keyed_proof_response(&db, StateTable::OilToken, id).await
+}
+
+// Handler to get the keyed merkle proof of a tokenization
+pub async fn get_tokenization_keyed_proof(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    keyed_proof_response(&db, StateTable::Tokenization, id).await
+}
+
+// Handler to get the keyed merkle proof of a comment
+pub async fn get_comment_keyed_proof(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    keyed_proof_response(&db, StateTable::Comment, id).await
+}
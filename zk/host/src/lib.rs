@@ -1,24 +1,57 @@
 use std::sync::Arc;
+use axum::extract::FromRef;
 use sea_orm::DatabaseConnection;
 use serde::{ Deserialize, Serialize };
 
 pub mod auth;
+pub mod block;
+pub mod clock;
+pub mod dry_run;
+pub mod eip712;
+pub mod error;
+pub mod job;
 pub mod jwt;
 pub mod oil_token;
 pub mod tokenization;
 pub mod comment;
 pub mod saved_token;
+pub mod siwe;
 pub mod overall;
+pub mod proof;
+pub mod prover;
 pub mod redis;
 pub mod sync_state;
+pub mod inclusion;
+pub mod transition;
+pub mod delta;
+pub mod keyed;
+pub mod state;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct SessionStats {
-    pub segments: u32,
+    pub segments: usize,
     pub total_cycles: u64,
     pub user_cycles: u64,
     pub paging_cycles: u64,
     pub reserved_cycles: u64,
 }
 
-pub type AppState = Arc<DatabaseConnection>;
+// Router state. Handlers take the part they need, State<Arc<DatabaseConnection>> or
+// State<SharedProver>.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Arc<DatabaseConnection>,
+    pub prover: prover::SharedProver,
+}
+
+impl FromRef<AppState> for Arc<DatabaseConnection> {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for prover::SharedProver {
+    fn from_ref(state: &AppState) -> Self {
+        state.prover.clone()
+    }
+}
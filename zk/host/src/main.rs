@@ -2,7 +2,6 @@ use std::process;
 use std::env;
 use std::sync::Arc;
 
-use axum::http::header::ACCESS_CONTROL_ALLOW_HEADERS;
 use axum::routing::{ get, post };
 use axum::{ middleware, Router };
 use dotenv::dotenv;
@@ -13,8 +12,21 @@ use host::tokenization::complete_tokenization_by_id;
 use host::comment::get_comment_by_oil_token_id;
 use host::overall::get_overall_state_handler;
 use host::overall::sync_state_handler;
-use host::sync_state;
-use tower_http::cors::ExposeHeaders;
+use host::delta::sync_delta_handler;
+use host::block::{ bootstrap_genesis, get_blocks_handler, verify_blocks_handler };
+use host::inclusion::{ get_comment_proof, get_oil_token_proof, get_tokenization_proof };
+use host::keyed::{
+    get_comment_keyed_proof,
+    get_oil_token_keyed_proof,
+    get_tokenization_keyed_proof,
+    init_keyed_handler,
+};
+use host::dry_run::init_overall_dry_run_handler;
+use host::eip712::signed_action;
+use host::job::{ get_job_handler, spawn_workers, worker_count };
+use host::proof::{ get_proof_handler, get_proofs_handler, record_proofs, verify_proof_handler };
+use host::prover::prover_from_env;
+use host::AppState;
 use tower_http::cors::{ Any, CorsLayer };
 
 use host::saved_token::create_saved_token;
@@ -28,7 +40,13 @@ use host::{
 
 use host::{
     overall::init_overall_handler,
-    auth::{ auth, verify_auth_handler, verify_signature_handler, get_verify_handler },
+    auth::{
+        auth,
+        verify_auth_handler,
+        verify_signature_handler,
+        get_verify_handler,
+        native_login_handler,
+    },
     tokenization::init_tokenization_handler,
     comment::init_comment_handler,
     oil_token::init_oil_token_handler,
@@ -46,6 +64,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let db = establish_connection(&database_url).await?;
     let db = Arc::new(db);
 
+    // every receipt proven here is kept in the proof table
+    let prover = record_proofs(db.clone(), prover_from_env()?);
+    let state = AppState { db: db.clone(), prover: prover.clone() };
+
+    // prove queued jobs in this process too, unless PROVER_WORKERS=0 leaves them to prover-worker
+    spawn_workers(db.clone(), prover, worker_count());
+
+    // a fresh database has no chain yet, transitions need its genesis block as their parent
+    if let Some(job) = bootstrap_genesis(&db).await? {
+        println!("No genesis block yet, proven by proof job {}", job);
+    }
+
     let cors = CorsLayer::new()
         .allow_origin(Any) // allow frontend origin
         .allow_methods(Any) // or restrict: .allow_methods([Method::GET, Method::POST])
@@ -59,8 +89,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/comments", post(create_comment))
         .route("/api/save_token", post(create_saved_token)) // save token
         .route("/api/tokenizations/{id}/complete", post(complete_tokenization_by_id)) // complete tokenization
+        .route_layer(middleware::from_fn(signed_action))
         .route_layer(middleware::from_fn(auth))
-        .with_state(db.clone());
+        .with_state(state.clone());
 
     let app = Router::new()
         .route("/api/oil_tokens/{id}", get(get_oil_token_by_id))
@@ -69,6 +100,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/tokenizations/{id}", get(get_tokenization_by_id))
         .route("/api/comments", get(get_comments))
         .route("/api/comments/{id}", get(get_comment_by_oil_token_id))
+        // merkle inclusion proofs
+        .route("/api/oil_tokens/{id}/proof", get(get_oil_token_proof))
+        .route("/api/tokenizations/{id}/proof", get(get_tokenization_proof))
+        .route("/api/comments/{id}/proof", get(get_comment_proof))
+        // keyed membership / non-membership proofs
+        .route("/api/oil_tokens/{id}/keyed_proof", get(get_oil_token_keyed_proof))
+        .route("/api/tokenizations/{id}/keyed_proof", get(get_tokenization_keyed_proof))
+        .route("/api/comments/{id}/keyed_proof", get(get_comment_keyed_proof))
         // save
         .route("/api/saved_tokens/{user}", get(get_saved_tokens_by_user)) // get user saved items
         .route("/api/tokens/saved/{id}", get(get_saved_tokens)) // get all saved by token_id
@@ -77,16 +116,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/oil_tokens/init", get(init_oil_token_handler))
         .route("/api/comments/init", get(init_comment_handler))
         .route("/api/db/init", get(init_overall_handler))
+        .route("/api/db/init/dry_run", get(init_overall_dry_run_handler))
+        .route("/api/db/init_keyed", get(init_keyed_handler))
 
         // verify auth
         .route("/api/auth", get(get_verify_handler))
         .route("/api/auth", post(verify_signature_handler))
         .route("/api/auth/verify", post(verify_auth_handler))
+        .route("/api/auth/login", post(native_login_handler))
         .route("/api/state", get(get_overall_state_handler))
         .route("/api/sync", get(sync_state_handler))
+        .route("/api/sync/delta", get(sync_delta_handler))
+        // chain of proven state blocks
+        .route("/api/blocks", get(get_blocks_handler))
+        .route("/api/blocks/verify", get(verify_blocks_handler))
+        // queued proofs
+        .route("/api/jobs/{id}", get(get_job_handler))
+        // stored proofs
+        .route("/api/proofs", get(get_proofs_handler))
+        .route("/api/proofs/{id}", get(get_proof_handler))
+        .route("/api/proofs/{id}/verify", get(verify_proof_handler))
         .merge(protected_routes)
         .layer(cors)
-        .with_state(db);
+        .with_state(state);
 
     // Initialize tracing. In order to view logs, run `RUST_LOG=info cargo run`
     tracing_subscriber
@@ -1,165 +1,138 @@
-use axum::{ http::StatusCode, Json };
-use oil_tokenization_core::OilTokenState;
+use axum::{ http::StatusCode, response::{ IntoResponse, Response }, Json };
+use oil_tokenization_core::{ Actor, Leaf, OilTokenAction, OilTokenRecord, Record };
 use chrono::Utc;
 use entity::{ oil_token, OilTokenModel };
-use ethers::types::Address;
-use methods::{ INIT_OIL_TOKEN_ELF, INIT_OIL_TOKEN_ID };
-use risc0_zkvm::{ default_prover, ExecutorEnv, Receipt };
-use sea_orm::{ ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait, QueryOrder };
-use serde::{ Deserialize, Serialize };
+use sea_orm::{ ActiveEnum, DatabaseConnection, DbErr, EntityTrait, QueryOrder };
 use serde_json::{ Value, json };
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
-use crate::{ auth::USER, jwt::Claims, SessionStats };
-
-pub fn get_oil_token_leaves(oil_tokens: &Vec<OilTokenModel>) -> Vec<String> {
-    let mut leaves = vec![];
-    for oil_token in oil_tokens {
-        let oil_token_record = format!(
-            "{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}",
-            oil_token.id,
-            oil_token.token_id,
-            oil_token.oil_type,
-            oil_token.grade,
-            oil_token.quantity,
-            oil_token.unit,
-            oil_token.location,
-            oil_token.certification,
-            oil_token.quality_report.to_string(),
-            oil_token.storage_conditions.to_string(),
-            oil_token.expiry_date.and_utc().timestamp(),
-            oil_token.current_price,
-            oil_token.reserve_price,
-            oil_token.status
-                .as_ref()
-                .map(|s| s.to_string())
-                .unwrap_or_default(),
-            oil_token.owner,
-            oil_token.created_at.and_utc().timestamp(),
-            oil_token.updated_at.and_utc().timestamp()
-        );
-        leaves.push(oil_token_record);
+use crate::{
+    auth::current_user,
+    dry_run::{ dry_run_transition, DryRunQuery },
+    error::ApiError,
+    job::{ enqueue_response, JobKind, JobStatus, ReceiptQuery },
+    prover::ProverBackend,
+    state::{ init_sub_state, SubStateCommit },
+    transition::{ next_id, queue_transition, RowInsert, StateTransition },
+};
+
+// compact JSON with the keys of every object sorted, so key order never changes a leaf.
+fn canonical_json(value: &Value) -> String {
+    fn sorted(value: &Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let map: BTreeMap<&String, Value> = map
+                    .iter()
+                    .map(|(key, value)| (key, sorted(value)))
+                    .collect();
+                json!(map)
+            }
+            Value::Array(items) => Value::Array(items.iter().map(sorted).collect()),
+            value => value.clone(),
+        }
     }
-    leaves
+    sorted(value).to_string()
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct OilTokenCommit {
-    pub receipt: Receipt,
-    pub stats: SessionStats,
-}
-
-impl OilTokenCommit {
-    pub fn get_commit(&self) -> Result<OilTokenState, String> {
-        let state = self.receipt.journal.decode().map_err(|e| e.to_string())?;
-        Ok(state)
+pub fn to_oil_token_record(oil_token: &OilTokenModel) -> OilTokenRecord {
+    OilTokenRecord {
+        id: oil_token.id,
+        token_id: oil_token.token_id,
+        oil_type: oil_token.oil_type.clone(),
+        grade: oil_token.grade.clone(),
+        quantity: oil_token.quantity.normalize().to_string(),
+        unit: oil_token.unit.clone(),
+        location: oil_token.location.clone(),
+        certification: oil_token.certification.clone(),
+        quality_report: canonical_json(&oil_token.quality_report),
+        storage_conditions: canonical_json(&oil_token.storage_conditions),
+        expiry_date: oil_token.expiry_date.and_utc().timestamp(),
+        current_price: oil_token.current_price.normalize().to_string(),
+        reserve_price: oil_token.reserve_price.normalize().to_string(),
+        status: oil_token.status.as_ref().map(|s| s.to_value()),
+        owner: oil_token.owner.clone(),
+        created_at: oil_token.created_at.and_utc().timestamp(),
+        updated_at: oil_token.updated_at.and_utc().timestamp(),
     }
+}
 
-    pub fn verify_and_get_commit(&self) -> Result<OilTokenState, String> {
-        self.receipt.verify(INIT_OIL_TOKEN_ID).map_err(|e| e.to_string())?;
-        self.get_commit()
-    }
+// Rows as (primary key, leaf), laid out in the table tree by slot_leaves.
+pub fn get_oil_token_leaves(oil_tokens: &[OilTokenModel]) -> Vec<(i32, Leaf)> {
+    oil_tokens
+        .iter()
+        .map(|oil_token| (oil_token.id, to_oil_token_record(oil_token).leaf()))
+        .collect()
 }
 
-pub fn init_oil_token(leaves: Vec<String>) -> Result<OilTokenCommit, String> {
-    let env = ExecutorEnv::builder().write(&leaves).unwrap().build().unwrap();
-    let prover = default_prover();
-    let prove_info = prover.prove(env, INIT_OIL_TOKEN_ELF).unwrap();
-    let oil_token_commit = OilTokenCommit {
-        receipt: prove_info.receipt,
-        stats: SessionStats {
-            segments: prove_info.stats.segments,
-            total_cycles: prove_info.stats.total_cycles,
-            user_cycles: prove_info.stats.user_cycles,
-            paging_cycles: prove_info.stats.paging_cycles,
-            reserved_cycles: prove_info.stats.reserved_cycles,
-        },
-    };
-    Ok(oil_token_commit)
+pub type OilTokenCommit = SubStateCommit<OilTokenAction>;
+
+pub fn init_oil_token(prover: &dyn ProverBackend, leaves: Vec<Leaf>) -> Result<OilTokenCommit, String> {
+    init_sub_state(prover, leaves)
 }
 
-pub async fn init_oil_token_handler(axum::extract::State(
-    db,
-): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
-    Json<Value>,
-    (axum::http::StatusCode, String)
-> {
-    let oil_tokens = get_all_oil_tokens(&db).await.unwrap();
-    let leaves = get_oil_token_leaves(&oil_tokens);
-    let result = init_oil_token(leaves).map_err(|e| (
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-        e.to_string(),
-    ))?;
-    Ok(Json(json!(result)))
+// Handler to queue the proof of the oil tokens table, see /api/jobs/{id}
+pub async fn init_oil_token_handler(
+    axum::extract::Query(query): axum::extract::Query<ReceiptQuery>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    enqueue_response(&db, JobKind::InitOilToken, None, query.receipt_kind).await
 }
 
 #[axum::debug_handler]
 pub async fn create_oil_token(
+    axum::extract::Query(query): axum::extract::Query<DryRunQuery>,
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>,
     Json(oil_token_data): Json<OilTokenModel>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
-    let current_user = USER.with(|user| Claims {
-        username: user.username.clone(),
-        addr: user.addr
-            .clone()
-            .parse::<Address>()
-            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid address".to_string()))
-            .unwrap()
-            .to_string(),
-        exp: user.exp,
-    });
+) -> Result<Response, ApiError> {
+    let current_user = current_user()?;
 
-    eprintln!("Request from user: {:?}", current_user.addr);
-    eprintln!("Request from username: {}", current_user.username);
-
-    use sea_orm::ActiveValue::Set;
-    let oil_token_id = oil_token::Entity
-        ::find()
-        .order_by_desc(oil_token::Column::Id)
-        .one(&*db).await
-        .unwrap()
-        .unwrap();
+    // the id is taken after the state is loaded, see next_id
+    let mut transition = StateTransition::load(&db).await?;
+    let oil_token_id = next_id(&db, "oil_token").await?;
     let now_naive: chrono::NaiveDateTime = Utc::now().naive_utc();
-    let oil_token_model = oil_token::ActiveModel {
-        id: Set(oil_token_id.id + 1),
-        token_id: Set(oil_token_data.token_id),
-        oil_type: Set(oil_token_data.oil_type.to_owned()),
-        grade: Set(oil_token_data.grade.to_owned()),
-        quantity: Set(oil_token_data.quantity),
-        unit: Set(oil_token_data.unit.to_owned()),
-        location: Set(oil_token_data.location.to_owned()),
-        certification: Set(oil_token_data.certification.to_owned()),
-        quality_report: Set(oil_token_data.quality_report.clone()),
-        storage_conditions: Set(oil_token_data.storage_conditions.clone()),
-        expiry_date: Set(oil_token_data.expiry_date),
-        current_price: Set(oil_token_data.current_price),
-        reserve_price: Set(oil_token_data.reserve_price),
-        status: Set(oil_token_data.status.clone()),
-        owner: Set(current_user.addr),
-        created_at: Set(now_naive.clone()),
-        updated_at: Set(now_naive),
-        ..Default::default()
+    let new_oil_token = OilTokenModel {
+        id: oil_token_id,
+        owner: current_user.addr,
+        created_at: now_naive,
+        updated_at: now_naive,
+        ..oil_token_data
     };
 
-    oil_token_model
-        .insert(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    Ok(Json(json!({
-      "status": "success",
-      "message": "Oil token created successfully"
-  })))
+    // prove the CREATE transition before the row is written
+    transition
+        .oil_token(OilTokenAction::CREATE, None, Some(to_oil_token_record(&new_oil_token)))
+        .map_err(ApiError::Validation)?;
+    // a dry run stops here, with what the proof would cost
+    if query.dry_run {
+        return Ok(dry_run_transition(&db, transition, Actor::SYSTEM).await?.into_response());
+    }
+    // proven by a prover worker, which stores the row with its block
+    let job_id = queue_transition(
+        &db,
+        transition,
+        Actor::SYSTEM,
+        RowInsert::OilToken(Box::new(new_oil_token))
+    ).await?;
+
+    let body = json!({
+        "status": "success",
+        "message": "Oil token queued, it is stored once job_id is done",
+        "id": oil_token_id,
+        "job_id": job_id,
+        "job_status": JobStatus::Queued.as_str()
+    });
+    Ok((StatusCode::ACCEPTED, Json(body)).into_response())
 }
 
 // Handler to get an oil token by ID
 pub async fn get_oil_token_by_id(
     axum::extract::Path(id): axum::extract::Path<i32>,
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<Value>, ApiError> {
     let oil_token = oil_token::Entity
         ::find_by_id(id)
-        .one(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .one(&*db).await?;
 
     match oil_token {
         Some(oil_token) =>
@@ -167,7 +140,7 @@ pub async fn get_oil_token_by_id(
         "status": "success",
         "data": oil_token
     }))),
-        None => Err((axum::http::StatusCode::NOT_FOUND, "Oil token not found".to_string())),
+        None => Err(ApiError::NotFound("Oil token not found".to_string())),
     }
 }
 
@@ -176,12 +149,9 @@ pub async fn get_all_oil_tokens_handler(axum::extract::State(
     db,
 ): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
     Json<Value>,
-    (axum::http::StatusCode, String)
+    ApiError
 > {
-    let oil_tokens = get_all_oil_tokens(&db).await.map_err(|e| (
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-        e.to_string(),
-    ))?;
+    let oil_tokens = get_all_oil_tokens(&db).await?;
 
     Ok(Json(json!({
         "status": "success",
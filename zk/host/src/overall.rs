@@ -1,80 +1,31 @@
-use axum::{ http::StatusCode, Json };
-use oil_tokenization_core::{ OverallState, OverallParams };
+use axum::Json;
+use oil_tokenization_core::{ slot_leaves, BlockHeader, Leaf, MerkleTree, OverallState, OverallParams };
 use chrono::Utc;
 use entity::{ oil_token, tokenization, comment };
-use methods::{ INIT_OVERALL_ELF, INIT_OVERALL_ID };
-use risc0_zkvm::{ default_prover, ExecutorEnv, Receipt };
+use methods::{ INIT_OVERALL_ID, INIT_STATE_ID };
+use risc0_zkvm::{ sha::Digest, Receipt };
 use sea_orm::{ DatabaseConnection, DbErr, EntityTrait, QueryOrder };
 use serde::{ Deserialize, Serialize };
 use serde_json::{ Value, json };
 use std::sync::Arc;
-
-use crate::{ SessionStats, sync_state };
-
-pub fn get_oil_token_leaves(oil_tokens: &Vec<::entity::oil_token::Model>) -> Vec<String> {
-    let mut leaves = vec![];
-    for oil_token in oil_tokens {
-        let oil_token_record = format!(
-            "{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}",
-            oil_token.id,
-            oil_token.token_id,
-            oil_token.oil_type,
-            oil_token.grade,
-            oil_token.quantity,
-            oil_token.unit,
-            oil_token.location,
-            oil_token.certification,
-            oil_token.quality_report.to_string(),
-            oil_token.storage_conditions.to_string(),
-            oil_token.expiry_date.and_utc().timestamp(),
-            oil_token.current_price,
-            oil_token.reserve_price,
-            oil_token.status
-                .as_ref()
-                .map(|s| s.to_string())
-                .unwrap_or_default(),
-            oil_token.owner,
-            oil_token.created_at.and_utc().timestamp(),
-            oil_token.updated_at.and_utc().timestamp()
-        );
-        leaves.push(oil_token_record);
-    }
-    leaves
-}
-
-pub fn get_tokenization_leaves(tokenizations: &Vec<::entity::tokenization::Model>) -> Vec<String> {
-    let mut leaves = vec![];
-    for tokenization in tokenizations {
-        let tokenization_record = format!(
-            "{}:{}:{}:{}:{}:{}",
-            tokenization.id,
-            tokenization.oil_token_id,
-            tokenization.tokenizer_id,
-            tokenization.amount,
-            tokenization.created_at.and_utc().timestamp(),
-            tokenization.updated_at.and_utc().timestamp()
-        );
-        leaves.push(tokenization_record);
-    }
-    leaves
-}
-
-pub fn get_comment_leaves(comments: &Vec<::entity::comment::Model>) -> Vec<String> {
-    let mut leaves = vec![];
-    for comment in comments {
-        let comment_record = format!(
-            "{}:{}:{}:{}:{}:{}",
-            comment.id,
-            comment.oil_token_id,
-            comment.user,
-            comment.content,
-            comment.created_at.and_utc().timestamp(),
-            comment.updated_at.and_utc().timestamp()
-        );
-        leaves.push(comment_record);
-    }
-    leaves
-}
+use std::thread::{ self, ScopedJoinHandle };
+
+use crate::{
+    block::{ head_block, store_block, BlockKind },
+    clock::next_timestamp,
+    comment::init_comment,
+    error::ApiError,
+    job::{ enqueue_response, JobKind, ReceiptQuery },
+    oil_token::init_oil_token,
+    proof::{ proof_by_journal, proof_cache_enabled },
+    prover::{ verify_receipt, Guest, ProveRequest, ProverBackend, ReceiptKind, SharedProver },
+    SessionStats,
+    tokenization::init_tokenization,
+};
+
+pub use crate::oil_token::get_oil_token_leaves;
+pub use crate::tokenization::get_tokenization_leaves;
+pub use crate::comment::get_comment_leaves;
 
 #[derive(Serialize, Deserialize)]
 pub struct OverallCommit {
@@ -84,78 +35,133 @@ pub struct OverallCommit {
 
 impl OverallCommit {
     pub fn get_commit(&self) -> Result<OverallState, String> {
-        let state = self.receipt.journal.decode().map_err(|e| e.to_string())?;
+        let (state_image_id, state): (Digest, OverallState) = self.receipt.journal
+            .decode()
+            .map_err(|e| e.to_string())?;
+        if state_image_id != Digest::from(INIT_STATE_ID) {
+            return Err("Sub-states were proven with another image".to_string());
+        }
         Ok(state)
     }
 
     pub fn verify_and_get_commit(&self) -> Result<OverallState, String> {
-        self.receipt.verify(INIT_OVERALL_ID).map_err(|e| e.to_string())?;
+        verify_receipt(&self.receipt, INIT_OVERALL_ID)?;
         self.get_commit()
     }
 }
 
+fn joined<T>(handle: ScopedJoinHandle<'_, Result<T, String>>) -> Result<T, String> {
+    handle.join().map_err(|_| "Sub-state prover panicked".to_string())?
+}
+
+// Proves the three tables in parallel, then composes their receipts into one succinct receipt.
+// Without a parent block the result is the genesis of the chain.
 pub fn init_overall(
-    oil_token_leaves: Vec<String>,
-    tokenization_leaves: Vec<String>,
-    comment_leaves: Vec<String>
+    prover: &dyn ProverBackend,
+    oil_token_leaves: Vec<Leaf>,
+    tokenization_leaves: Vec<Leaf>,
+    comment_leaves: Vec<Leaf>,
+    parent: Option<BlockHeader>
 ) -> Result<OverallCommit, String> {
+    let (oil_token, tokenization, comment) = thread::scope(|scope| {
+        let oil_token = scope.spawn(move || init_oil_token(prover, oil_token_leaves));
+        let tokenization = scope.spawn(move || init_tokenization(prover, tokenization_leaves));
+        let comment = init_comment(prover, comment_leaves)?;
+        Ok::<_, String>((joined(oil_token)?, joined(tokenization)?, comment))
+    })?;
+
+    let timestamp = next_timestamp(parent.map(|p| p.updated_at).unwrap_or_default());
     let params = OverallParams {
-        oil_token_leaves,
-        tokenization_leaves,
-        comment_leaves,
+        state_image_id: Digest::from(INIT_STATE_ID),
+        oil_token_state: oil_token.get_commit()?,
+        tokenization_state: tokenization.get_commit()?,
+        comment_state: comment.get_commit()?,
+        parent,
+        timestamp,
     };
-    let env = ExecutorEnv::builder().write(&params).unwrap().build().unwrap();
-    let prover = default_prover();
-    let prove_info = prover.prove(env, INIT_OVERALL_ELF).unwrap();
+    let request = ProveRequest::new(Guest::InitOverall, &params)?
+        .assumption(oil_token.receipt)
+        .assumption(tokenization.receipt)
+        .assumption(comment.receipt)
+        .receipt_kind(ReceiptKind::Succinct);
+    let proof = prover.prove(&request)?;
     let overall_commit = OverallCommit {
-        receipt: prove_info.receipt,
-        stats: SessionStats {
-            segments: prove_info.stats.segments,
-            total_cycles: prove_info.stats.total_cycles,
-            user_cycles: prove_info.stats.user_cycles,
-            paging_cycles: prove_info.stats.paging_cycles,
-            reserved_cycles: prove_info.stats.reserved_cycles,
-        },
+        receipt: proof.receipt,
+        stats: proof.stats,
     };
     Ok(overall_commit)
 }
 
-pub async fn init_overall_handler(axum::extract::State(
-    db,
-): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
-    Json<Value>,
-    (axum::http::StatusCode, String)
-> {
-    let oil_tokens = get_all_oil_tokens(&db).await.unwrap();
-    let tokenizations = get_all_tokenizations(&db).await.unwrap();
-    let comments = get_all_comments(&db).await.unwrap();
-
-    let oil_token_leaves = get_oil_token_leaves(&oil_tokens);
-    let tokenization_leaves = get_tokenization_leaves(&tokenizations);
-    let comment_leaves = get_comment_leaves(&comments);
-
-    let result = init_overall(oil_token_leaves, tokenization_leaves, comment_leaves).map_err(|e| (
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-        e.to_string(),
-    ))?;
-    Ok(Json(json!(result)))
+// Proves the full state on top of the chain head, or as its genesis, and stores the block.
+// Sub-state proofs of unchanged tables come from the proof table.
+pub async fn commit_overall(
+    db: &DatabaseConnection,
+    prover: SharedProver,
+    oil_token_leaves: Vec<Leaf>,
+    tokenization_leaves: Vec<Leaf>,
+    comment_leaves: Vec<Leaf>
+) -> Result<OverallCommit, ApiError> {
+    let head = head_block(db).await.map_err(ApiError::Internal)?;
+    // an unchanged state is already proven by the head block, which is returned as is
+    // instead of a new block with the same root
+    let root = OverallState::compute_root(
+        &MerkleTree::new(&oil_token_leaves).root(),
+        &MerkleTree::new(&tokenization_leaves).root(),
+        &MerkleTree::new(&comment_leaves).root()
+    );
+    if let Some((BlockKind::Overall, receipt, header)) = &head {
+        if header.root == root && proof_cache_enabled() {
+            let cached = proof_by_journal(db, Guest::InitOverall, receipt).await.map_err(
+                ApiError::Internal
+            )?;
+            if let Some(proof) = cached {
+                return Ok(OverallCommit { receipt: proof.receipt, stats: proof.stats });
+            }
+        }
+    }
+    let parent = head.map(|(_, _, header)| header);
+    // proving blocks for seconds to minutes, keep it off the async runtime
+    let overall_commit = tokio::task
+        ::spawn_blocking(move || {
+            init_overall(&*prover, oil_token_leaves, tokenization_leaves, comment_leaves, parent)
+        })
+        .await?
+        .map_err(ApiError::Prover)?;
+    let state = overall_commit.get_commit().map_err(ApiError::Internal)?;
+    store_block(db, BlockKind::Overall, &state.block, &overall_commit.receipt).await?;
+    Ok(overall_commit)
+}
+
+// Handler to queue the proof of the full state, see /api/jobs/{id}
+pub async fn init_overall_handler(
+    axum::extract::Query(query): axum::extract::Query<ReceiptQuery>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    enqueue_response(&db, JobKind::InitOverall, None, query.receipt_kind).await
 }
 
 pub async fn get_overall_state_handler(axum::extract::State(
     db,
 ): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
     Json<Value>,
-    (axum::http::StatusCode, String)
+    ApiError
 > {
-    let oil_tokens = get_all_oil_tokens(&db).await.unwrap();
-    let tokenizations = get_all_tokenizations(&db).await.unwrap();
-    let comments = get_all_comments(&db).await.unwrap();
+    let oil_tokens = get_all_oil_tokens(&db).await?;
+    let tokenizations = get_all_tokenizations(&db).await?;
+    let comments = get_all_comments(&db).await?;
 
-    let oil_token_leaves = get_oil_token_leaves(&oil_tokens);
-    let tokenization_leaves = get_tokenization_leaves(&tokenizations);
-    let comment_leaves = get_comment_leaves(&comments);
+    let oil_token_leaves = slot_leaves(&get_oil_token_leaves(&oil_tokens)).map_err(ApiError::Internal)?;
+    let tokenization_leaves = slot_leaves(&get_tokenization_leaves(&tokenizations)).map_err(
+        ApiError::Internal
+    )?;
+    let comment_leaves = slot_leaves(&get_comment_leaves(&comments)).map_err(ApiError::Internal)?;
 
-    let overall_state = OverallState::init(oil_token_leaves, tokenization_leaves, comment_leaves);
+    let overall_state = OverallState::init(
+        oil_token_leaves,
+        tokenization_leaves,
+        comment_leaves,
+        Utc::now().timestamp()
+    );
 
     Ok(Json(json!({
         "status": "success",
@@ -163,41 +169,12 @@ pub async fn get_overall_state_handler(axum::extract::State(
     })))
 }
 
-pub async fn sync_state_handler(axum::extract::State(
-    db,
-): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
-    Json<Value>,
-    (axum::http::StatusCode, String)
-> {
-    let oil_tokens = get_all_oil_tokens(&db).await.unwrap();
-    let tokenizations = get_all_tokenizations(&db).await.unwrap();
-    let comments = get_all_comments(&db).await.unwrap();
-
-    let oil_token_leaves = get_oil_token_leaves(&oil_tokens);
-    let tokenization_leaves = get_tokenization_leaves(&tokenizations);
-    let comment_leaves = get_comment_leaves(&comments);
-
-    let overall_commit = init_overall(
-        oil_token_leaves,
-        tokenization_leaves,
-        comment_leaves
-    ).map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    let hash = sync_state(&overall_commit).await.map_err(|e| (
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-        e.to_string(),
-    ))?;
-
-    Ok(
-        Json(
-            json!({
-        "status": "success",
-        "hash": hash,
-        "receipt": overall_commit.receipt,
-        "stats": overall_commit.stats
-    })
-        )
-    )
+// Handler to queue the proof of the full state and its upload to Pinata, see /api/jobs/{id}
+pub async fn sync_state_handler(
+    axum::extract::Query(query): axum::extract::Query<ReceiptQuery>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    enqueue_response(&db, JobKind::Sync, None, query.receipt_kind).await
 }
 
 // Function to get all oil tokens from database
@@ -0,0 +1,374 @@
+use axum::Json;
+use chrono::Utc;
+use dotenv::dotenv;
+use entity::proof;
+use oil_tokenization_core::{
+    CommentAction,
+    DeltaState,
+    KeyedState,
+    OilTokenAction,
+    OverallState,
+    StateTable,
+    SubState,
+    TokenizationAction,
+    TransitionState,
+    VerifyCommit as VerifyState,
+};
+use risc0_zkvm::{ sha::{ Digest, Digestible }, Journal, Receipt };
+use sea_orm::{
+    sea_query::Expr,
+    ColumnTrait,
+    Condition,
+    DatabaseConnection,
+    DbErr,
+    EntityTrait,
+    QueryFilter,
+    QueryOrder,
+    QuerySelect,
+};
+use serde::Deserialize;
+use serde_json::{ Value, json };
+use std::env;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{ unbounded_channel, UnboundedReceiver, UnboundedSender };
+
+use crate::error::ApiError;
+use crate::prover::{
+    verify_receipt,
+    CompressRequest,
+    Guest,
+    Proof,
+    ProveRequest,
+    ProverBackend,
+    ReceiptKind,
+    SharedProver,
+};
+
+// A receipt on its way to the proof table.
+struct ProofRecord {
+    guest: Guest,
+    fingerprint: String,
+    receipt: Receipt,
+    stats: Value,
+}
+
+// Wraps a backend and stores every receipt it produces in the proof table. A request
+// proven before is answered from the table instead: its fingerprint covers the leaves it
+// proves, so a write to a table changes the fingerprint and the next request is proven.
+// Backends are called from blocking code, so rows are written by a task of their own.
+// The cache is looked up with `Handle::block_on`, which panics on a runtime worker thread:
+// like any backend it must only be called from a blocking thread, such as `spawn_blocking`.
+pub struct RecordingProver {
+    inner: SharedProver,
+    db: Arc<DatabaseConnection>,
+    runtime: Handle,
+    cache: bool,
+    records: UnboundedSender<ProofRecord>,
+}
+
+impl RecordingProver {
+    // Stored proof with this fingerprint, or the proof made by `prove`, which is stored.
+    fn cached_or(
+        &self,
+        guest: Guest,
+        fingerprint: String,
+        prove: impl FnOnce() -> Result<Proof, String>
+    ) -> Result<Proof, String> {
+        if self.cache {
+            match self.runtime.block_on(cached_proof(&self.db, guest, &fingerprint)) {
+                Ok(Some(proof)) => {
+                    return Ok(proof);
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Proof cache lookup failed, proving instead: {}", e),
+            }
+        }
+        let proof = prove()?;
+        let record = ProofRecord {
+            guest,
+            fingerprint,
+            receipt: proof.receipt.clone(),
+            stats: json!(proof.stats),
+        };
+        if self.records.send(record).is_err() {
+            tracing::warn!(guest = guest.as_str(), "Proof recorder stopped, receipt not stored");
+        }
+        Ok(proof)
+    }
+}
+
+impl ProverBackend for RecordingProver {
+    fn prove(&self, request: &ProveRequest) -> Result<Proof, String> {
+        self.cached_or(request.guest, request.fingerprint()?, || self.inner.prove(request))
+    }
+
+    fn compress(&self, request: CompressRequest) -> Result<Proof, String> {
+        self.cached_or(request.guest, request.fingerprint()?, || self.inner.compress(request))
+    }
+}
+
+// Whether stored proofs answer repeated requests, from PROOF_CACHE. On unless set to 0.
+pub fn proof_cache_enabled() -> bool {
+    dotenv().ok();
+    env::var("PROOF_CACHE").map_or(true, |cache| cache != "0" && cache != "false")
+}
+
+// Returns `prover` wrapped so its receipts are stored in `db`, and reused when the cache
+// is enabled. Must be called from within the runtime, the returned prover from blocking
+// threads only.
+pub fn record_proofs(db: Arc<DatabaseConnection>, prover: SharedProver) -> SharedProver {
+    let (records, receiver) = unbounded_channel();
+    tokio::spawn(store_proofs(db.clone(), receiver));
+    Arc::new(RecordingProver {
+        inner: prover,
+        db,
+        runtime: Handle::current(),
+        cache: proof_cache_enabled(),
+        records,
+    })
+}
+
+async fn store_proofs(db: Arc<DatabaseConnection>, mut receiver: UnboundedReceiver<ProofRecord>) {
+    while let Some(record) = receiver.recv().await {
+        let guest = record.guest.as_str();
+        if let Err(e) = store_proof(&db, record).await {
+            tracing::error!(guest, "Failed to store proof: {}", e);
+        }
+    }
+}
+
+async fn store_proof(db: &DatabaseConnection, record: ProofRecord) -> Result<proof::Model, DbErr> {
+    use sea_orm::ActiveModelTrait;
+    use sea_orm::ActiveValue::{ NotSet, Set };
+    // a journal this host cannot decode is still stored, its receipt stays verifiable
+    let journal = decode_journal(record.guest, &record.receipt.journal)
+        .map_err(|e| tracing::warn!(guest = record.guest.as_str(), "Failed to decode journal: {}", e))
+        .ok();
+    let row = proof::ActiveModel {
+        id: NotSet,
+        guest: Set(record.guest.as_str().to_string()),
+        image_id: Set(Digest::from(record.guest.image_id()).to_string()),
+        journal_digest: Set(record.receipt.journal.digest().to_string()),
+        journal: Set(journal),
+        receipt: Set(json!(record.receipt)),
+        receipt_kind: Set(
+            ReceiptKind::of(&record.receipt)
+                .map_or("fake", |kind| kind.as_str())
+                .to_string()
+        ),
+        stats: Set(record.stats),
+        fingerprint: Set(record.fingerprint),
+        created_at: Set(Utc::now().naive_utc()),
+        verified_at: Set(None),
+    };
+    row.insert(db).await
+}
+
+// Newest stored proof of `guest` matching `filter`, if its receipt still verifies. One that
+// no longer does, such as a dev-mode receipt once ACCEPT_DEV_RECEIPTS is unset, is not
+// used: the request is proven again and the new proof becomes the newest.
+async fn verified_proof(
+    db: &DatabaseConnection,
+    guest: Guest,
+    filter: Condition
+) -> Result<Option<Proof>, String> {
+    let Some(row) = proof::Entity
+        ::find()
+        .filter(proof::Column::Guest.eq(guest.as_str()))
+        .filter(filter)
+        .order_by_desc(proof::Column::Id)
+        .one(db).await
+        .map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let Ok(receipt) = serde_json::from_value::<Receipt>(row.receipt) else {
+        return Ok(None);
+    };
+    if verify_receipt(&receipt, guest.image_id()).is_err() {
+        return Ok(None);
+    }
+    let stats = serde_json::from_value(row.stats).map_err(|e| e.to_string())?;
+    proof::Entity
+        ::update_many()
+        .col_expr(proof::Column::VerifiedAt, Expr::value(Utc::now().naive_utc()))
+        .filter(proof::Column::Id.eq(row.id))
+        .exec(db).await
+        .map_err(|e| e.to_string())?;
+    Ok(Some(Proof { receipt, stats }))
+}
+
+// Stored proof of the request with this fingerprint.
+pub async fn cached_proof(
+    db: &DatabaseConnection,
+    guest: Guest,
+    fingerprint: &str
+) -> Result<Option<Proof>, String> {
+    verified_proof(db, guest, Condition::all().add(proof::Column::Fingerprint.eq(fingerprint))).await
+}
+
+// Stored proof that committed this journal, which finds the stats of a receipt kept elsewhere.
+pub async fn proof_by_journal(
+    db: &DatabaseConnection,
+    guest: Guest,
+    receipt: &Receipt
+) -> Result<Option<Proof>, String> {
+    let digest = receipt.journal.digest().to_string();
+    verified_proof(db, guest, Condition::all().add(proof::Column::JournalDigest.eq(digest))).await
+}
+
+fn sub_state<A: oil_tokenization_core::Action>(journal: &Journal) -> Result<Value, String> {
+    let (table, state): (StateTable, SubState<A>) = journal
+        .decode()
+        .map_err(|e| e.to_string())?;
+    Ok(json!({ "table": table, "state": state }))
+}
+
+// Journal of `guest`, decoded into the type the guest commits.
+pub fn decode_journal(guest: Guest, journal: &Journal) -> Result<Value, String> {
+    match guest {
+        Guest::InitState => {
+            // the action type depends on the table, which comes first in the journal
+            let table: StateTable = journal.decode().map_err(|e| e.to_string())?;
+            match table {
+                StateTable::OilToken => sub_state::<OilTokenAction>(journal),
+                StateTable::Tokenization => sub_state::<TokenizationAction>(journal),
+                StateTable::Comment => sub_state::<CommentAction>(journal),
+            }
+        }
+        Guest::InitOverall => {
+            let (state_image_id, state): (Digest, OverallState) = journal
+                .decode()
+                .map_err(|e| e.to_string())?;
+            Ok(json!({ "state_image_id": state_image_id.to_string(), "state": state }))
+        }
+        Guest::InitKeyed => {
+            let state: KeyedState = journal.decode().map_err(|e| e.to_string())?;
+            Ok(json!(state))
+        }
+        Guest::Transition => {
+            let state: TransitionState = journal.decode().map_err(|e| e.to_string())?;
+            Ok(json!(state))
+        }
+        Guest::SyncDelta => {
+            let state: DeltaState = journal.decode().map_err(|e| e.to_string())?;
+            Ok(json!(state))
+        }
+        Guest::Verify => {
+            let state: VerifyState = journal.decode().map_err(|e| e.to_string())?;
+            Ok(json!(state))
+        }
+    }
+}
+
+// Proofs listed per page unless `limit` asks for another number, and at most.
+const DEFAULT_PROOF_LIMIT: u64 = 50;
+const MAX_PROOF_LIMIT: u64 = 200;
+
+#[derive(Deserialize)]
+pub struct ProofQuery {
+    guest: Option<String>,
+    limit: Option<u64>,
+    #[serde(default)]
+    offset: u64,
+}
+
+// Handler to list a page of the stored proofs, newest first and without their receipts
+pub async fn get_proofs_handler(
+    axum::extract::Query(query): axum::extract::Query<ProofQuery>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    let mut select = proof::Entity
+        ::find()
+        .select_only()
+        .columns([
+            proof::Column::Id,
+            proof::Column::Guest,
+            proof::Column::ImageId,
+            proof::Column::JournalDigest,
+            proof::Column::Journal,
+            proof::Column::ReceiptKind,
+            proof::Column::Stats,
+            proof::Column::Fingerprint,
+            proof::Column::CreatedAt,
+            proof::Column::VerifiedAt,
+        ])
+        .order_by_desc(proof::Column::Id);
+    let limit = query.limit.unwrap_or(DEFAULT_PROOF_LIMIT);
+    if !(1..=MAX_PROOF_LIMIT).contains(&limit) {
+        return Err(ApiError::Validation(format!("limit must be between 1 and {}", MAX_PROOF_LIMIT)));
+    }
+    if let Some(guest) = query.guest {
+        let guest = Guest::parse(&guest).map_err(ApiError::Validation)?;
+        select = select.filter(proof::Column::Guest.eq(guest.as_str()));
+    }
+    let proofs = select
+        .limit(limit)
+        .offset(query.offset)
+        .into_json()
+        .all(&*db).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "limit": limit,
+        "offset": query.offset,
+        "data": proofs
+    })))
+}
+
+async fn find_proof(db: &DatabaseConnection, id: i32) -> Result<proof::Model, ApiError> {
+    proof::Entity
+        ::find_by_id(id)
+        .one(db).await?
+        .ok_or_else(|| ApiError::NotFound("Proof not found".to_string()))
+}
+
+// Handler to get a stored proof with its receipt
+pub async fn get_proof_handler(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    let proof = find_proof(&db, id).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": proof
+    })))
+}
+
+// Handler to verify a stored receipt again against the image of its guest
+pub async fn verify_proof_handler(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    use sea_orm::ActiveModelTrait;
+    use sea_orm::ActiveValue::Set;
+    let proof = find_proof(&db, id).await?;
+    let guest = Guest::parse(&proof.guest).map_err(ApiError::Internal)?;
+    let receipt: Receipt = serde_json
+        ::from_value(proof.receipt.clone())
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+
+    // the row must still describe its receipt, and the receipt must still verify
+    if receipt.journal.digest().to_string() != proof.journal_digest {
+        return Err(
+            ApiError::Verification("Journal digest does not match the receipt".to_string())
+        );
+    }
+    verify_receipt(&receipt, guest.image_id()).map_err(ApiError::Verification)?;
+    let journal = decode_journal(guest, &receipt.journal).map_err(ApiError::Verification)?;
+
+    let mut proof: proof::ActiveModel = proof.into();
+    proof.verified_at = Set(Some(Utc::now().naive_utc()));
+    let proof = proof
+        .update(&*db).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "verified": true,
+        "id": proof.id,
+        "guest": proof.guest,
+        "journal": journal,
+        "verified_at": proof.verified_at
+    })))
+}
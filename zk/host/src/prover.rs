@@ -0,0 +1,516 @@
+use dotenv::dotenv;
+use methods::{
+    INIT_KEYED_ELF,
+    INIT_KEYED_ID,
+    INIT_OVERALL_ELF,
+    INIT_OVERALL_ID,
+    INIT_STATE_ELF,
+    INIT_STATE_ID,
+    SYNC_DELTA_ELF,
+    SYNC_DELTA_ID,
+    TRANSITION_ELF,
+    TRANSITION_ID,
+    VERIFY_ELF,
+    VERIFY_ID,
+};
+use risc0_zkvm::{
+    default_executor,
+    default_prover,
+    sha::{ Digest, Digestible },
+    ExecutorEnv,
+    FakeReceipt,
+    InnerReceipt,
+    Journal,
+    ProverOpts,
+    Receipt,
+    SessionInfo,
+};
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest as _, Sha256 };
+use std::env;
+use std::sync::{ Arc, Mutex };
+use std::time::Duration;
+
+use crate::SessionStats;
+
+// How long a remote prover waits for an answer unless PROVER_TIMEOUT_SECS says otherwise.
+const DEFAULT_PROVER_TIMEOUT_SECS: u64 = 3600;
+
+// Guest a proof runs, so a request names it without carrying the ELF.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Guest {
+    InitState,
+    InitOverall,
+    InitKeyed,
+    Transition,
+    SyncDelta,
+    Verify,
+}
+
+impl Guest {
+    pub fn elf(&self) -> &'static [u8] {
+        match self {
+            Guest::InitState => INIT_STATE_ELF,
+            Guest::InitOverall => INIT_OVERALL_ELF,
+            Guest::InitKeyed => INIT_KEYED_ELF,
+            Guest::Transition => TRANSITION_ELF,
+            Guest::SyncDelta => SYNC_DELTA_ELF,
+            Guest::Verify => VERIFY_ELF,
+        }
+    }
+
+    pub fn image_id(&self) -> [u32; 8] {
+        match self {
+            Guest::InitState => INIT_STATE_ID,
+            Guest::InitOverall => INIT_OVERALL_ID,
+            Guest::InitKeyed => INIT_KEYED_ID,
+            Guest::Transition => TRANSITION_ID,
+            Guest::SyncDelta => SYNC_DELTA_ID,
+            Guest::Verify => VERIFY_ID,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Guest::InitState => "init_state",
+            Guest::InitOverall => "init_overall",
+            Guest::InitKeyed => "init_keyed",
+            Guest::Transition => "transition",
+            Guest::SyncDelta => "sync_delta",
+            Guest::Verify => "verify",
+        }
+    }
+
+    pub fn parse(guest: &str) -> Result<Self, String> {
+        match guest {
+            "init_state" => Ok(Guest::InitState),
+            "init_overall" => Ok(Guest::InitOverall),
+            "init_keyed" => Ok(Guest::InitKeyed),
+            "transition" => Ok(Guest::Transition),
+            "sync_delta" => Ok(Guest::SyncDelta),
+            "verify" => Ok(Guest::Verify),
+            _ => Err(format!("Unknown guest {}", guest)),
+        }
+    }
+}
+
+// Shape of the receipt a proof produces, from the largest to the smallest. A receipt can
+// be compressed into a later kind, never back.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptKind {
+    // one receipt per segment
+    #[default]
+    Composite,
+    // segments recursed into one receipt, needed to compose receipts in another guest
+    Succinct,
+    // succinct receipt wrapped in a Groth16 proof, small enough for an on-chain verifier
+    Groth16,
+}
+
+impl ReceiptKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReceiptKind::Composite => "composite",
+            ReceiptKind::Succinct => "succinct",
+            ReceiptKind::Groth16 => "groth16",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Result<Self, String> {
+        match kind {
+            "composite" => Ok(ReceiptKind::Composite),
+            "succinct" => Ok(ReceiptKind::Succinct),
+            "groth16" => Ok(ReceiptKind::Groth16),
+            _ => Err(format!("Unknown receipt kind {}", kind)),
+        }
+    }
+
+    // Kind of `receipt`, None for a dev-mode fake receipt, which has no real kind.
+    pub fn of(receipt: &Receipt) -> Option<Self> {
+        match receipt.inner {
+            InnerReceipt::Composite(_) => Some(ReceiptKind::Composite),
+            InnerReceipt::Succinct(_) => Some(ReceiptKind::Succinct),
+            InnerReceipt::Groth16(_) => Some(ReceiptKind::Groth16),
+            _ => None,
+        }
+    }
+
+    pub fn opts(&self) -> ProverOpts {
+        match self {
+            ReceiptKind::Composite => ProverOpts::composite(),
+            ReceiptKind::Succinct => ProverOpts::succinct(),
+            ReceiptKind::Groth16 => ProverOpts::groth16(),
+        }
+    }
+}
+
+// Everything a backend needs to prove a guest. It is plain data, so it can be sent to a
+// remote prover as is.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ProveRequest {
+    pub guest: Guest,
+    // guest input, as written by ExecutorEnv::write
+    pub input: Vec<u32>,
+    // receipts the guest verifies with env::verify
+    pub assumptions: Vec<Receipt>,
+    pub receipt_kind: ReceiptKind,
+}
+
+impl ProveRequest {
+    pub fn new<T: Serialize>(guest: Guest, input: &T) -> Result<Self, String> {
+        Ok(ProveRequest {
+            guest,
+            input: risc0_zkvm::serde::to_vec(input).map_err(|e| e.to_string())?,
+            assumptions: vec![],
+            receipt_kind: ReceiptKind::default(),
+        })
+    }
+
+    pub fn assumption(mut self, receipt: Receipt) -> Self {
+        self.assumptions.push(receipt);
+        self
+    }
+
+    pub fn receipt_kind(mut self, receipt_kind: ReceiptKind) -> Self {
+        self.receipt_kind = receipt_kind;
+        self
+    }
+
+    // sha256 of everything the receipt depends on: the guest image, its input, the claims
+    // of its assumptions and the receipt kind. Equal requests prove the same journal.
+    pub fn fingerprint(&self) -> Result<String, String> {
+        let mut hasher = Sha256::new();
+        hasher.update(Digest::from(self.guest.image_id()).as_bytes());
+        hasher.update(self.receipt_kind.as_str().as_bytes());
+        for receipt in &self.assumptions {
+            let claim = receipt.claim().map_err(|e| e.to_string())?;
+            hasher.update(claim.digest().as_bytes());
+        }
+        for word in &self.input {
+            hasher.update(word.to_le_bytes());
+        }
+        Ok(Digest::from_bytes(hasher.finalize().into()).to_string())
+    }
+
+    fn env(&self) -> Result<ExecutorEnv<'static>, String> {
+        let mut builder = ExecutorEnv::builder();
+        for receipt in &self.assumptions {
+            builder.add_assumption(receipt.clone());
+        }
+        builder
+            .write_slice(&self.input)
+            .build()
+            .map_err(|e| e.to_string())
+    }
+}
+
+// A receipt to compress into `receipt_kind`. Compression keeps the journal, and the
+// stats of the execution that was proven.
+#[derive(Deserialize, Serialize)]
+pub struct CompressRequest {
+    pub guest: Guest,
+    pub receipt: Receipt,
+    pub stats: SessionStats,
+    pub receipt_kind: ReceiptKind,
+}
+
+impl CompressRequest {
+    // sha256 of the claim of the receipt and the kind it is compressed into.
+    pub fn fingerprint(&self) -> Result<String, String> {
+        let claim = self.receipt.claim().map_err(|e| e.to_string())?;
+        let mut hasher = Sha256::new();
+        hasher.update(claim.digest().as_bytes());
+        hasher.update(self.receipt_kind.as_str().as_bytes());
+        Ok(Digest::from_bytes(hasher.finalize().into()).to_string())
+    }
+}
+
+// Receipt of a proven request, the `receipt` and `stats` of every *Commit.
+#[derive(Serialize, Deserialize)]
+pub struct Proof {
+    pub receipt: Receipt,
+    pub stats: SessionStats,
+}
+
+// Journal and cycle counts of a guest run in the executor, without a proof.
+pub struct Execution {
+    pub guest: Guest,
+    pub journal: Journal,
+    pub stats: ExecutionStats,
+}
+
+// Cycle counts of an execution. The executor only counts the user cycles of each segment,
+// so it cannot tell paging from the rest of what a proof pads its segments with. All of
+// it is counted as overhead.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct ExecutionStats {
+    pub segments: usize,
+    pub total_cycles: u64,
+    pub user_cycles: u64,
+    pub overhead_cycles: u64,
+}
+
+impl ExecutionStats {
+    fn of(info: &SessionInfo) -> Self {
+        let user_cycles = info.cycles();
+        let total_cycles = info.segments
+            .iter()
+            .map(|segment| 1u64 << segment.po2)
+            .sum::<u64>();
+        ExecutionStats {
+            segments: info.segments.len(),
+            total_cycles,
+            user_cycles,
+            overhead_cycles: total_cycles.saturating_sub(user_cycles),
+        }
+    }
+}
+
+// Where proofs are made. Calls block for as long as the proof takes, so async code runs
+// them on a blocking task.
+pub trait ProverBackend: Send + Sync {
+    fn prove(&self, request: &ProveRequest) -> Result<Proof, String>;
+
+    fn compress(&self, request: CompressRequest) -> Result<Proof, String>;
+}
+
+pub type SharedProver = Arc<dyn ProverBackend>;
+
+fn prove_with_opts(request: &ProveRequest, opts: &ProverOpts) -> Result<Proof, String> {
+    let prover = default_prover();
+    let prove_info = prover
+        .prove_with_opts(request.env()?, request.guest.elf(), opts)
+        .map_err(|e| e.to_string())?;
+    Ok(Proof {
+        receipt: prove_info.receipt,
+        stats: SessionStats {
+            segments: prove_info.stats.segments,
+            total_cycles: prove_info.stats.total_cycles,
+            user_cycles: prove_info.stats.user_cycles,
+            paging_cycles: prove_info.stats.paging_cycles,
+            reserved_cycles: prove_info.stats.reserved_cycles,
+        },
+    })
+}
+
+fn compress_with_opts(request: CompressRequest, opts: &ProverOpts) -> Result<Proof, String> {
+    let receipt = default_prover()
+        .compress(opts, &request.receipt)
+        .map_err(|e| e.to_string())?;
+    Ok(Proof { receipt, stats: request.stats })
+}
+
+// Proves on this machine with the default risc0 prover.
+pub struct LocalProver;
+
+impl ProverBackend for LocalProver {
+    fn prove(&self, request: &ProveRequest) -> Result<Proof, String> {
+        prove_with_opts(request, &request.receipt_kind.opts())
+    }
+
+    fn compress(&self, request: CompressRequest) -> Result<Proof, String> {
+        let opts = request.receipt_kind.opts();
+        compress_with_opts(request, &opts)
+    }
+}
+
+// Runs the guest without proving and returns a fake receipt of its journal, for tests and
+// local development. Fake receipts only verify with RISC0_DEV_MODE and ACCEPT_DEV_RECEIPTS set.
+pub struct DevModeProver;
+
+impl ProverBackend for DevModeProver {
+    fn prove(&self, request: &ProveRequest) -> Result<Proof, String> {
+        prove_with_opts(request, &request.receipt_kind.opts().with_dev_mode(true))
+    }
+
+    // fake receipts stay as they are
+    fn compress(&self, request: CompressRequest) -> Result<Proof, String> {
+        let opts = request.receipt_kind.opts().with_dev_mode(true);
+        compress_with_opts(request, &opts)
+    }
+}
+
+// Runs requests in the executor instead of proving them, for dry runs. It answers with
+// fake receipts of what a proof would claim, so composed guests run on top of them, and
+// keeps every execution. Execution is cheap next to proving, so it always runs here.
+#[derive(Default)]
+pub struct ExecutingProver {
+    executions: Mutex<Vec<Execution>>,
+}
+
+impl ExecutingProver {
+    pub fn into_executions(self) -> Vec<Execution> {
+        self.executions.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ProverBackend for ExecutingProver {
+    fn prove(&self, request: &ProveRequest) -> Result<Proof, String> {
+        let info = default_executor()
+            .execute(request.env()?, request.guest.elf())
+            .map_err(|e| e.to_string())?;
+        let claim = info.receipt_claim
+            .clone()
+            .ok_or_else(|| format!("{} execution has no claim", request.guest.as_str()))?;
+        let receipt = Receipt::new(
+            InnerReceipt::Fake(FakeReceipt::new(claim)),
+            info.journal.bytes.clone()
+        );
+        self.executions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Execution {
+                guest: request.guest,
+                journal: info.journal.clone(),
+                stats: ExecutionStats::of(&info),
+            });
+        // the fake receipt only feeds the guests composed on it, its stats are never reported
+        let stats = SessionStats {
+            segments: info.segments.len(),
+            total_cycles: 0,
+            user_cycles: 0,
+            paging_cycles: 0,
+            reserved_cycles: 0,
+        };
+        Ok(Proof { receipt, stats })
+    }
+
+    // nothing to compress without a proof
+    fn compress(&self, request: CompressRequest) -> Result<Proof, String> {
+        Ok(Proof { receipt: request.receipt, stats: request.stats })
+    }
+}
+
+// Sends requests to a prover server, such as the prover-server binary, at `url`, with the
+// shared `token` it expects.
+pub struct RemoteProver {
+    url: String,
+    token: String,
+    client: reqwest::blocking::Client,
+}
+
+impl RemoteProver {
+    // `timeout` bounds a whole request, proving included, so a server that stopped
+    // answering fails the job instead of holding its worker forever.
+    pub fn new(url: &str, token: &str, timeout: Duration) -> Result<Self, String> {
+        let client = reqwest::blocking::Client
+            ::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| format!("Failed to build the prover client: {}", e))?;
+        Ok(RemoteProver {
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            client,
+        })
+    }
+}
+
+impl RemoteProver {
+    fn post<T: Serialize>(&self, path: &str, request: &T) -> Result<Proof, String> {
+        let response = self.client
+            .post(format!("{}/{}", self.url, path))
+            .bearer_auth(&self.token)
+            .json(request)
+            .send()
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response.text().unwrap_or_default();
+            return Err(format!("Remote prover answered {}: {}", status, message));
+        }
+        response.json().map_err(|e| e.to_string())
+    }
+}
+
+impl ProverBackend for RemoteProver {
+    fn prove(&self, request: &ProveRequest) -> Result<Proof, String> {
+        self.post("prove", request)
+    }
+
+    fn compress(&self, request: CompressRequest) -> Result<Proof, String> {
+        self.post("compress", &request)
+    }
+}
+
+// Backend chosen by PROVER: `local` (default), `dev` or `remote`, which sends proofs to
+// PROVER_URL with the PROVER_TOKEN of that server. Backends that make fake receipts need ACCEPT_DEV_RECEIPTS, they would
+// otherwise prove nothing the server accepts.
+pub fn prover_from_env() -> Result<SharedProver, String> {
+    dotenv().ok();
+    let prover = env::var("PROVER").unwrap_or_else(|_| "local".to_string());
+    let dev_mode = prover == "dev" || env::var("RISC0_DEV_MODE").is_ok_and(|v| v == "1" || v == "true");
+    if dev_mode && !dev_receipts_accepted() {
+        return Err(
+            "Dev mode makes fake receipts, set ACCEPT_DEV_RECEIPTS=1 to accept them".to_string()
+        );
+    }
+    match prover.as_str() {
+        "local" => Ok(Arc::new(LocalProver)),
+        "dev" => Ok(Arc::new(DevModeProver)),
+        "remote" => {
+            let url = env::var("PROVER_URL").map_err(|_| "PROVER=remote needs PROVER_URL".to_string())?;
+            let token = prover_token()?;
+            Ok(Arc::new(RemoteProver::new(&url, &token, prover_timeout())?))
+        }
+        prover => Err(format!("Unknown PROVER {}, expected local, dev or remote", prover)),
+    }
+}
+
+// How long a remote prover waits for the server to answer a request, from
+// PROVER_TIMEOUT_SECS. Proofs take minutes, the default leaves them an hour.
+pub fn prover_timeout() -> Duration {
+    dotenv().ok();
+    let seconds = env::var("PROVER_TIMEOUT_SECS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(DEFAULT_PROVER_TIMEOUT_SECS);
+    Duration::from_secs(seconds)
+}
+
+// Shared secret between a remote prover and its prover server, from PROVER_TOKEN.
+pub fn prover_token() -> Result<String, String> {
+    dotenv().ok();
+    env::var("PROVER_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| "PROVER_TOKEN is not set, the prover server needs it".to_string())
+}
+
+// Whether dev-mode fake receipts are accepted, from ACCEPT_DEV_RECEIPTS. They prove nothing,
+// so only local development opts in to them.
+pub fn dev_receipts_accepted() -> bool {
+    dotenv().ok();
+    env::var("ACCEPT_DEV_RECEIPTS").is_ok_and(|v| v == "1" || v == "true")
+}
+
+// Verifies `receipt` against `image_id`. Fake receipts are refused unless dev receipts are
+// accepted, whatever RISC0_DEV_MODE says.
+pub fn verify_receipt(receipt: &Receipt, image_id: impl Into<Digest>) -> Result<(), String> {
+    oil_tokenization_core::verify_receipt(receipt, image_id.into(), dev_receipts_accepted())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    #[test]
+    fn remote_prover_gives_up_on_a_server_that_never_answers() {
+        // takes the connection and never answers it
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let _connection = listener.accept();
+            std::thread::sleep(Duration::from_secs(10));
+        });
+        let prover = RemoteProver::new(&url, "token", Duration::from_millis(200)).unwrap();
+        let request = ProveRequest::new(Guest::Verify, &0u32).unwrap();
+        let started = Instant::now();
+        assert!(prover.prove(&request).is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
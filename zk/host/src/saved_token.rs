@@ -1,51 +1,47 @@
-use axum::{ http::StatusCode, Json };
+use axum::Json;
 use chrono::Utc;
 use entity::{ saved_token, SavedTokenModel };
-use ethers::types::Address;
-use sea_orm::{ ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait, QueryOrder };
+use sea_orm::{
+    ActiveModelTrait,
+    ColumnTrait,
+    DatabaseConnection,
+    DbErr,
+    EntityTrait,
+    QueryFilter,
+    QueryOrder,
+};
 use serde_json::{ Value, json };
 use std::sync::Arc;
 
-use crate::{ auth::USER, jwt::Claims };
+use crate::{ auth::current_user, error::ApiError };
 
 #[axum::debug_handler]
 pub async fn create_saved_token(
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>,
     Json(saved_token_data): Json<SavedTokenModel>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
-    let current_user = USER.with(|user| Claims {
-        username: user.username.clone(),
-        addr: user.addr
-            .clone()
-            .parse::<Address>()
-            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid address".to_string()))
-            .unwrap()
-            .to_string(),
-        exp: user.exp,
-    });
+) -> Result<Json<Value>, ApiError> {
+    let current_user = current_user()?;
 
     eprintln!("Request from user: {:?}", current_user.addr);
     eprintln!("Request from username: {}", current_user.username);
 
     use sea_orm::ActiveValue::Set;
+    // ids start at 1 in an empty table
     let saved_token_id = saved_token::Entity
         ::find()
         .order_by_desc(saved_token::Column::Id)
-        .one(&*db).await
-        .unwrap()
-        .unwrap();
+        .one(&*db).await?
+        .map_or(1, |last| last.id + 1);
     let now_naive: chrono::NaiveDateTime = Utc::now().naive_utc();
     let saved_token_model = saved_token::ActiveModel {
-        id: Set(saved_token_id.id + 1),
+        id: Set(saved_token_id),
         user: Set(current_user.addr),
         oil_token_id: Set(saved_token_data.oil_token_id),
         created_at: Set(now_naive),
-        ..Default::default()
     };
 
     saved_token_model
-        .insert(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .insert(&*db).await?;
 
     Ok(Json(json!({
       "status": "success",
@@ -57,13 +53,12 @@ pub async fn create_saved_token(
 pub async fn get_saved_tokens_by_user(
     axum::extract::Path(user): axum::extract::Path<String>,
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<Value>, ApiError> {
     let saved_tokens = saved_token::Entity
         ::find()
         .filter(saved_token::Column::User.eq(user))
         .order_by_desc(saved_token::Column::CreatedAt)
-        .all(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .all(&*db).await?;
 
     Ok(Json(json!({
         "status": "success",
@@ -75,13 +70,12 @@ pub async fn get_saved_tokens_by_user(
 pub async fn get_saved_tokens(
     axum::extract::Path(id): axum::extract::Path<i32>,
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<Value>, ApiError> {
     let saved_tokens = saved_token::Entity
         ::find()
         .filter(saved_token::Column::OilTokenId.eq(id))
         .order_by_desc(saved_token::Column::CreatedAt)
-        .all(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .all(&*db).await?;
 
     Ok(Json(json!({
         "status": "success",
@@ -0,0 +1,51 @@
+use chrono::Utc;
+use dotenv::dotenv;
+use oil_tokenization_core::SiweMessage;
+use std::env;
+
+// Lifetime of a login message before it has to be signed again.
+pub const LOGIN_TTL_SECONDS: i64 = 300;
+// How long after it was submitted a login proven by the verify guest can be exchanged for
+// a token. The queued proof may well take longer than the message lives.
+pub const LOGIN_PROOF_TTL_SECONDS: i64 = 3600;
+
+// Domain, URI and chain every login message is scoped to.
+#[derive(Clone, Debug)]
+pub struct SiweConfig {
+    pub domain: String,
+    pub uri: String,
+    pub chain_id: u64,
+}
+
+impl SiweConfig {
+    pub fn from_env() -> Self {
+        dotenv().ok();
+        let domain = env::var("SIWE_DOMAIN").unwrap_or_else(|_| "localhost:3001".to_string());
+        SiweConfig {
+            uri: env::var("SIWE_URI").unwrap_or_else(|_| format!("http://{}", domain)),
+            chain_id: env
+                ::var("SIWE_CHAIN_ID")
+                .ok()
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(1),
+            domain,
+        }
+    }
+
+    // Login message for `address` (EIP-55 checksummed), valid for LOGIN_TTL_SECONDS.
+    pub fn login_message(&self, address: &str, nonce: &str) -> SiweMessage {
+        let now = Utc::now().timestamp();
+        SiweMessage {
+            domain: self.domain.clone(),
+            address: address.to_string(),
+            statement: Some("Sign in to the Oil Tokenization Platform".to_string()),
+            uri: self.uri.clone(),
+            version: "1".to_string(),
+            chain_id: self.chain_id,
+            nonce: nonce.to_string(),
+            issued_at: now,
+            expiration_time: Some(now + LOGIN_TTL_SECONDS),
+            not_before: None,
+        }
+    }
+}
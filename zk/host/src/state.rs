@@ -0,0 +1,51 @@
+use oil_tokenization_core::{ Action, Leaf, StateTable, SubState, SubStateParams };
+use methods::INIT_STATE_ID;
+use risc0_zkvm::Receipt;
+use serde::{ Deserialize, Serialize };
+use std::marker::PhantomData;
+
+use crate::{ prover::{ verify_receipt, Guest, ProveRequest, ProverBackend }, SessionStats };
+
+// Receipt of the init_state guest for the table of `A`.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct SubStateCommit<A: Action> {
+    pub receipt: Receipt,
+    pub stats: SessionStats,
+    #[serde(skip)]
+    action: PhantomData<A>,
+}
+
+impl<A: Action> SubStateCommit<A> {
+    pub fn get_commit(&self) -> Result<SubState<A>, String> {
+        let (table, state): (StateTable, SubState<A>) = self.receipt.journal
+            .decode()
+            .map_err(|e| e.to_string())?;
+        if table != A::TABLE {
+            return Err(format!("Receipt commits {:?}, expected {:?}", table, A::TABLE));
+        }
+        Ok(state)
+    }
+
+    pub fn verify_and_get_commit(&self) -> Result<SubState<A>, String> {
+        verify_receipt(&self.receipt, INIT_STATE_ID)?;
+        self.get_commit()
+    }
+}
+
+pub fn init_sub_state<A: Action>(
+    prover: &dyn ProverBackend,
+    leaves: Vec<Leaf>
+) -> Result<SubStateCommit<A>, String> {
+    let params = SubStateParams {
+        table: A::TABLE,
+        leaves,
+    };
+    let proof = prover.prove(&ProveRequest::new(Guest::InitState, &params)?)?;
+    let sub_state_commit = SubStateCommit {
+        receipt: proof.receipt,
+        stats: proof.stats,
+        action: PhantomData,
+    };
+    Ok(sub_state_commit)
+}
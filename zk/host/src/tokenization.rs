@@ -1,144 +1,113 @@
-use axum::{ http::StatusCode, Json };
-use oil_tokenization_core::TokenizationState;
+use axum::{ http::StatusCode, response::{ IntoResponse, Response }, Json };
+use oil_tokenization_core::{ Actor, Leaf, Record, TokenizationAction, TokenizationRecord };
 use chrono::Utc;
 use entity::{ tokenization, TokenizationModel };
-use ethers::types::Address;
-use methods::{ INIT_TOKENIZATION_ELF, INIT_TOKENIZATION_ID };
-use risc0_zkvm::{ default_prover, ExecutorEnv, Receipt };
-use sea_orm::{ ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait, QueryOrder };
-use serde::{ Deserialize, Serialize };
+use sea_orm::{ DatabaseConnection, DbErr, EntityTrait, QueryOrder };
 use serde_json::{ Value, json };
 use std::sync::Arc;
 
-use crate::{ auth::USER, jwt::Claims, SessionStats };
-
-pub fn get_tokenization_leaves(tokenizations: &Vec<TokenizationModel>) -> Vec<String> {
-    let mut leaves = vec![];
-    for tokenization in tokenizations {
-        let tokenization_record = format!(
-            "{}:{}:{}:{}:{}:{}",
-            tokenization.id,
-            tokenization.oil_token_id,
-            tokenization.tokenizer_id,
-            tokenization.amount,
-            tokenization.created_at.and_utc().timestamp(),
-            tokenization.updated_at.and_utc().timestamp()
-        );
-        leaves.push(tokenization_record);
+use crate::{
+    auth::current_user,
+    dry_run::{ dry_run_transition, DryRunQuery },
+    error::ApiError,
+    job::{ enqueue_response, JobKind, JobStatus, ReceiptQuery },
+    prover::ProverBackend,
+    state::{ init_sub_state, SubStateCommit },
+    transition::{ next_id, queue_transition, RowInsert, StateTransition },
+};
+
+pub fn to_tokenization_record(tokenization: &TokenizationModel) -> TokenizationRecord {
+    TokenizationRecord {
+        id: tokenization.id,
+        oil_token_id: tokenization.oil_token_id,
+        tokenizer_id: tokenization.tokenizer_id.clone(),
+        amount: tokenization.amount.normalize().to_string(),
+        created_at: tokenization.created_at.and_utc().timestamp(),
+        updated_at: tokenization.updated_at.and_utc().timestamp(),
     }
-    leaves
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct TokenizationCommit {
-    pub receipt: Receipt,
-    pub stats: SessionStats,
+// Rows as (primary key, leaf), laid out in the table tree by slot_leaves.
+pub fn get_tokenization_leaves(tokenizations: &[TokenizationModel]) -> Vec<(i32, Leaf)> {
+    tokenizations
+        .iter()
+        .map(|tokenization| (tokenization.id, to_tokenization_record(tokenization).leaf()))
+        .collect()
 }
 
-impl TokenizationCommit {
-    pub fn get_commit(&self) -> Result<TokenizationState, String> {
-        let state = self.receipt.journal.decode().map_err(|e| e.to_string())?;
-        Ok(state)
-    }
-
-    pub fn verify_and_get_commit(&self) -> Result<TokenizationState, String> {
-        self.receipt.verify(INIT_TOKENIZATION_ID).map_err(|e| e.to_string())?;
-        self.get_commit()
-    }
-}
+pub type TokenizationCommit = SubStateCommit<TokenizationAction>;
 
-pub fn init_tokenization(leaves: Vec<String>) -> Result<TokenizationCommit, String> {
-    let env = ExecutorEnv::builder().write(&leaves).unwrap().build().unwrap();
-    let prover = default_prover();
-    let prove_info = prover.prove(env, INIT_TOKENIZATION_ELF).unwrap();
-    let tokenization_commit = TokenizationCommit {
-        receipt: prove_info.receipt,
-        stats: SessionStats {
-            segments: prove_info.stats.segments,
-            total_cycles: prove_info.stats.total_cycles,
-            user_cycles: prove_info.stats.user_cycles,
-            paging_cycles: prove_info.stats.paging_cycles,
-            reserved_cycles: prove_info.stats.reserved_cycles,
-        },
-    };
-    Ok(tokenization_commit)
+pub fn init_tokenization(prover: &dyn ProverBackend, leaves: Vec<Leaf>) -> Result<TokenizationCommit, String> {
+    init_sub_state(prover, leaves)
 }
 
-pub async fn init_tokenization_handler(axum::extract::State(
-    db,
-): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
-    Json<Value>,
-    (axum::http::StatusCode, String)
-> {
-    let tokenizations = get_all_tokenizations(&db).await.unwrap();
-    let leaves = get_tokenization_leaves(&tokenizations);
-    let result = init_tokenization(leaves).map_err(|e| (
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-        e.to_string(),
-    ))?;
-    Ok(Json(json!(result)))
+// Handler to queue the proof of the tokenizations table, see /api/jobs/{id}
+pub async fn init_tokenization_handler(
+    axum::extract::Query(query): axum::extract::Query<ReceiptQuery>,
+    axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
+) -> Result<Json<Value>, ApiError> {
+    enqueue_response(&db, JobKind::InitTokenization, None, query.receipt_kind).await
 }
 
 #[axum::debug_handler]
 pub async fn create_tokenization(
+    axum::extract::Query(query): axum::extract::Query<DryRunQuery>,
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>,
     Json(tokenization_data): Json<TokenizationModel>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
-    let current_user = USER.with(|user| Claims {
-        username: user.username.clone(),
-        addr: user.addr
-            .clone()
-            .parse::<Address>()
-            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid address".to_string()))
-            .unwrap()
-            .to_string(),
-        exp: user.exp,
-    });
-
-    eprintln!("Request from user: {:?}", current_user.addr);
-    eprintln!("Request from username: {}", current_user.username);
+) -> Result<Response, ApiError> {
+    let current_user = current_user()?;
 
-    use sea_orm::ActiveValue::Set;
-    let tokenization_id = tokenization::Entity
-        ::find()
-        .order_by_desc(tokenization::Column::Id)
-        .one(&*db).await
-        .unwrap()
-        .unwrap();
+    // the id is taken after the state is loaded, see next_id
+    let mut transition = StateTransition::load(&db).await?;
+    let tokenization_id = next_id(&db, "tokenization").await?;
     let now_naive: chrono::NaiveDateTime = Utc::now().naive_utc();
-    let tokenization_model = tokenization::ActiveModel {
-        id: Set(tokenization_id.id + 1),
-        oil_token_id: Set(tokenization_data.oil_token_id),
-        tokenizer_id: Set(current_user.addr),
-        amount: Set(tokenization_data.amount),
-        created_at: Set(now_naive.clone()),
-        updated_at: Set(now_naive),
-        ..Default::default()
+    let new_tokenization = TokenizationModel {
+        id: tokenization_id,
+        oil_token_id: tokenization_data.oil_token_id,
+        tokenizer_id: current_user.addr,
+        amount: tokenization_data.amount,
+        created_at: now_naive,
+        updated_at: now_naive,
     };
 
-    tokenization_model
-        .insert(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    Ok(
-        Json(
-            json!({
-      "status": "success",
-      "message": "Tokenization created successfully"
-  })
+    // prove the CREATE transition before the row is written
+    transition
+        .tokenization(
+            TokenizationAction::CREATE,
+            None,
+            Some(to_tokenization_record(&new_tokenization))
         )
-    )
+        .map_err(ApiError::Validation)?;
+    // a dry run stops here, with what the proof would cost
+    if query.dry_run {
+        return Ok(dry_run_transition(&db, transition, Actor::SYSTEM).await?.into_response());
+    }
+    // proven by a prover worker, which stores the row with its block
+    let job_id = queue_transition(
+        &db,
+        transition,
+        Actor::SYSTEM,
+        RowInsert::Tokenization(Box::new(new_tokenization))
+    ).await?;
+
+    let body = json!({
+        "status": "success",
+        "message": "Tokenization queued, it is stored once job_id is done",
+        "id": tokenization_id,
+        "job_id": job_id,
+        "job_status": JobStatus::Queued.as_str()
+    });
+    Ok((StatusCode::ACCEPTED, Json(body)).into_response())
 }
 
 // Handler to get a tokenization by ID
 pub async fn get_tokenization_by_id(
     axum::extract::Path(id): axum::extract::Path<i32>,
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<Value>, ApiError> {
     let tokenization = tokenization::Entity
         ::find_by_id(id)
-        .one(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .one(&*db).await?;
 
     match tokenization {
         Some(tokenization) =>
@@ -146,7 +115,7 @@ pub async fn get_tokenization_by_id(
         "status": "success",
         "data": tokenization
     }))),
-        None => Err((axum::http::StatusCode::NOT_FOUND, "Tokenization not found".to_string())),
+        None => Err(ApiError::NotFound("Tokenization not found".to_string())),
     }
 }
 
@@ -155,12 +124,9 @@ pub async fn get_tokenizations(axum::extract::State(
     db,
 ): axum::extract::State<Arc<sea_orm::DatabaseConnection>>) -> Result<
     Json<Value>,
-    (axum::http::StatusCode, String)
+    ApiError
 > {
-    let tokenizations = get_all_tokenizations(&db).await.map_err(|e| (
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-        e.to_string(),
-    ))?;
+    let tokenizations = get_all_tokenizations(&db).await?;
 
     Ok(Json(json!({
         "status": "success",
@@ -179,11 +145,10 @@ pub async fn get_all_tokenizations(
 pub async fn complete_tokenization_by_id(
     axum::extract::Path(id): axum::extract::Path<i32>,
     axum::extract::State(db): axum::extract::State<Arc<sea_orm::DatabaseConnection>>
-) -> Result<Json<Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<Value>, ApiError> {
     let tokenization = tokenization::Entity
         ::find_by_id(id)
-        .one(&*db).await
-        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .one(&*db).await?;
 
     match tokenization {
         Some(tokenization) => {
@@ -199,6 +164,6 @@ pub async fn complete_tokenization_by_id(
                 )
             )
         }
-        None => Err((axum::http::StatusCode::NOT_FOUND, "Tokenization not found".to_string())),
+        None => Err(ApiError::NotFound("Tokenization not found".to_string())),
     }
 }
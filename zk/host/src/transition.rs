@@ -0,0 +1,557 @@
+use oil_tokenization_core::{
+    merkle::hash_leaf,
+    Action,
+    Actor,
+    BlockHeader,
+    CommentAction,
+    CommentRecord,
+    slot,
+    Leaf,
+    MerkleProof,
+    MerkleTree,
+    OilTokenAction,
+    OilTokenRecord,
+    Record,
+    SubStateRoot,
+    TableRoots,
+    TokenizationAction,
+    TokenizationRecord,
+    Transition,
+    TransitionParams,
+    TransitionState,
+};
+use entity::{ comment, oil_token, tokenization, CommentModel, OilTokenModel, TokenizationModel };
+use methods::TRANSITION_ID;
+use risc0_zkvm::{ sha::Digest, Receipt };
+use sea_orm::{
+    ActiveModelTrait,
+    ConnectionTrait,
+    DatabaseConnection,
+    DbBackend,
+    DbErr,
+    Statement,
+    TransactionTrait,
+};
+use serde::{ Deserialize, Serialize };
+use serde_json::json;
+
+use crate::{
+    block::{ parent_block, store_block, BlockKind },
+    clock::next_timestamp,
+    comment::get_comment_leaves,
+    error::ApiError,
+    job::{ enqueue_job, JobKind },
+    oil_token::get_oil_token_leaves,
+    overall::{ get_all_comments, get_all_oil_tokens, get_all_tokenizations },
+    prover::{ verify_receipt, Guest, ProveRequest, ProverBackend, ReceiptKind, SharedProver },
+    tokenization::get_tokenization_leaves,
+    SessionStats,
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct TransitionCommit {
+    pub receipt: Receipt,
+    pub stats: SessionStats,
+}
+
+impl TransitionCommit {
+    pub fn get_commit(&self) -> Result<TransitionState, String> {
+        let state = self.receipt.journal.decode().map_err(|e| e.to_string())?;
+        Ok(state)
+    }
+
+    pub fn verify_and_get_commit(&self) -> Result<TransitionState, String> {
+        verify_receipt(&self.receipt, TRANSITION_ID)?;
+        self.get_commit()
+    }
+}
+
+// Index of the slot of the row `id` in its table tree.
+fn slot_index(id: i32) -> Result<usize, String> {
+    slot(id)
+        .map(|index| index as usize)
+        .ok_or_else(|| format!("Record {} has no slot", id))
+}
+
+// One committed table: its tree with every row at the slot of its id, and how many rows it holds.
+#[derive(Clone)]
+pub(crate) struct StagedTable {
+    tree: MerkleTree,
+    leaves: u64,
+}
+
+impl StagedTable {
+    pub(crate) fn new(rows: &[(i32, Leaf)]) -> Result<Self, String> {
+        Self::from_hashes(
+            rows
+                .iter()
+                .map(|(id, leaf)| (*id, hash_leaf(leaf)))
+                .collect()
+        )
+    }
+
+    pub(crate) fn from_hashes(rows: Vec<(i32, Digest)>) -> Result<Self, String> {
+        let mut leaf_hashes = vec![];
+        for (id, leaf_hash) in &rows {
+            let index = slot_index(*id)?;
+            if index >= leaf_hashes.len() {
+                leaf_hashes.resize(index + 1, Digest::ZERO);
+            }
+            if leaf_hashes[index] != Digest::ZERO {
+                return Err(format!("Record {} is stored twice", id));
+            }
+            leaf_hashes[index] = *leaf_hash;
+        }
+        Ok(StagedTable {
+            tree: MerkleTree::from_hashes(leaf_hashes),
+            leaves: rows.len() as u64,
+        })
+    }
+
+    pub(crate) fn root(&self) -> SubStateRoot {
+        SubStateRoot {
+            root: self.tree.root(),
+            leaves: self.leaves,
+        }
+    }
+
+    // Rows of the table as (primary key, leaf hash), in slot order.
+    pub(crate) fn rows(&self) -> Vec<(i32, Digest)> {
+        self.tree
+            .leaf_hashes()
+            .iter()
+            .enumerate()
+            .filter(|(_, leaf_hash)| **leaf_hash != Digest::ZERO)
+            .map(|(index, leaf_hash)| ((index as i32) + 1, *leaf_hash))
+            .collect()
+    }
+
+    pub(crate) fn leaf_hash(&self, id: i32) -> Option<Digest> {
+        let index = slot(id)? as usize;
+        self.tree
+            .leaf_hashes()
+            .get(index)
+            .copied()
+            .filter(|leaf_hash| *leaf_hash != Digest::ZERO)
+    }
+
+    // Path of the slot of the row `id` and its leaf hash before the change, after which the
+    // change is applied to the tree. A created row fills its empty slot and a deleted row
+    // empties it, no other row moves.
+    pub(crate) fn stage(
+        &mut self,
+        id: i32,
+        after: Option<Leaf>
+    ) -> Result<(MerkleProof, Digest), String> {
+        let index = slot_index(id)?;
+        let old_leaf = self.leaf_hash(id).unwrap_or(Digest::ZERO);
+        let new_leaf = after.map(|leaf| hash_leaf(&leaf)).unwrap_or(Digest::ZERO);
+        if old_leaf == Digest::ZERO && new_leaf == Digest::ZERO {
+            return Err(format!("No leaf for record {}", id));
+        }
+        let proof = self.tree
+            .proof(index)
+            .ok_or_else(|| format!("No slot for record {}", id))?;
+        self.tree.set(index, new_leaf)?;
+        if old_leaf == Digest::ZERO {
+            self.leaves += 1;
+        } else if new_leaf == Digest::ZERO {
+            self.leaves -= 1;
+        }
+        Ok((proof, old_leaf))
+    }
+}
+
+// checks one mutation against the rules of its table and stages it, returning its path.
+fn stage_action<A: Action>(
+    table: &mut StagedTable,
+    action: &A,
+    before: Option<&A::Record>,
+    after: Option<&A::Record>
+) -> Result<MerkleProof, String> {
+    action.process(before, after)?;
+    let id = after
+        .or(before)
+        .map(|r| r.id())
+        .ok_or_else(|| format!("Missing {:?} record", A::TABLE))?;
+    let stored = table.leaf_hash(id).unwrap_or(Digest::ZERO);
+    if stored != before.map(|r| hash_leaf(&r.leaf())).unwrap_or(Digest::ZERO) {
+        return Err(format!("{:?} record {} does not match the stored row", A::TABLE, id));
+    }
+    let (proof, _) = table.stage(id, after.map(|r| r.leaf()))?;
+    Ok(proof)
+}
+
+// Collects the mutations of one request together with their Merkle paths,
+// ready to be proven by the transition guest.
+pub struct StateTransition {
+    roots: TableRoots,
+    oil_token: StagedTable,
+    tokenization: StagedTable,
+    comment: StagedTable,
+    actions: Vec<Transition>,
+}
+
+impl StateTransition {
+    pub async fn load(db: &DatabaseConnection) -> Result<Self, ApiError> {
+        let oil_tokens = get_all_oil_tokens(db).await?;
+        let tokenizations = get_all_tokenizations(db).await?;
+        let comments = get_all_comments(db).await?;
+        Self::new(
+            &get_oil_token_leaves(&oil_tokens),
+            &get_tokenization_leaves(&tokenizations),
+            &get_comment_leaves(&comments)
+        ).map_err(ApiError::Internal)
+    }
+
+    // Starts from the given rows of the three tables, as (primary key, leaf).
+    pub(crate) fn new(
+        oil_token: &[(i32, Leaf)],
+        tokenization: &[(i32, Leaf)],
+        comment: &[(i32, Leaf)]
+    ) -> Result<Self, String> {
+        let oil_token = StagedTable::new(oil_token)?;
+        let tokenization = StagedTable::new(tokenization)?;
+        let comment = StagedTable::new(comment)?;
+        Ok(StateTransition {
+            roots: TableRoots {
+                oil_token: oil_token.root(),
+                tokenization: tokenization.root(),
+                comment: comment.root(),
+            },
+            oil_token,
+            tokenization,
+            comment,
+            actions: vec![],
+        })
+    }
+
+    pub fn oil_token(
+        &mut self,
+        action: OilTokenAction,
+        before: Option<OilTokenRecord>,
+        after: Option<OilTokenRecord>
+    ) -> Result<(), String> {
+        let proof = stage_action(&mut self.oil_token, &action, before.as_ref(), after.as_ref())?;
+        self.actions.push(Transition::OilToken {
+            action,
+            proof,
+            before: before.map(Box::new),
+            after: after.map(Box::new),
+        });
+        Ok(())
+    }
+
+    pub fn tokenization(
+        &mut self,
+        action: TokenizationAction,
+        before: Option<TokenizationRecord>,
+        after: Option<TokenizationRecord>
+    ) -> Result<(), String> {
+        let proof = stage_action(&mut self.tokenization, &action, before.as_ref(), after.as_ref())?;
+        self.actions.push(Transition::Tokenization {
+            action,
+            proof,
+            before: before.map(Box::new),
+            after: after.map(Box::new),
+        });
+        Ok(())
+    }
+
+    pub fn comment(
+        &mut self,
+        action: CommentAction,
+        before: Option<CommentRecord>,
+        after: Option<CommentRecord>
+    ) -> Result<(), String> {
+        let proof = stage_action(&mut self.comment, &action, before.as_ref(), after.as_ref())?;
+        self.actions.push(Transition::Comment {
+            action,
+            proof,
+            before: before.map(Box::new),
+            after: after.map(Box::new),
+        });
+        Ok(())
+    }
+
+    // Stages `actions` again on this state, with the paths of its tables instead of those
+    // they were staged with.
+    pub fn restage(&mut self, actions: Vec<Transition>) -> Result<(), String> {
+        for transition in actions {
+            match transition {
+                Transition::OilToken { action, before, after, .. } => {
+                    self.oil_token(action, before.map(|r| *r), after.map(|r| *r))?;
+                }
+                Transition::Tokenization { action, before, after, .. } => {
+                    self.tokenization(action, before.map(|r| *r), after.map(|r| *r))?;
+                }
+                Transition::Comment { action, before, after, .. } => {
+                    self.comment(action, before.map(|r| *r), after.map(|r| *r))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn params(self, parent: BlockHeader, actor: Actor) -> TransitionParams {
+        TransitionParams {
+            parent,
+            timestamp: next_timestamp(parent.updated_at),
+            roots: self.roots,
+            actor,
+            actions: self.actions,
+        }
+    }
+}
+
+pub fn prove_transition(
+    prover: &dyn ProverBackend,
+    params: TransitionParams
+) -> Result<TransitionCommit, String> {
+    let proof = prover.prove(&ProveRequest::new(Guest::Transition, &params)?)?;
+    let transition_commit = TransitionCommit {
+        receipt: proof.receipt,
+        stats: proof.stats,
+    };
+    Ok(transition_commit)
+}
+
+// Params of `transition` on top of the chain head, which must be the state it starts from.
+pub async fn head_params(
+    db: &DatabaseConnection,
+    transition: StateTransition,
+    actor: Actor
+) -> Result<TransitionParams, ApiError> {
+    let parent = parent_block(db).await?;
+    let params = transition.params(parent, actor);
+    if params.roots.root() != parent.root {
+        return Err(
+            ApiError::Conflict("State has diverged from the chain head, sync it first".to_string())
+        );
+    }
+    Ok(params)
+}
+
+// Next primary key of `table`, from its id sequence, which also gives the slot the new row
+// is staged at. An id is never handed out twice, so the slot is still empty.
+pub async fn next_id(db: &DatabaseConnection, table: &str) -> Result<i32, DbErr> {
+    let row = db
+        .query_one(
+            Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "SELECT nextval(pg_get_serial_sequence($1, 'id'))::int4 AS id",
+                [table.into()]
+            )
+        ).await?
+        .ok_or_else(|| DbErr::RecordNotFound(format!("No id sequence for {}", table)))?;
+    row.try_get("", "id")
+}
+
+// Row created by a transition, written in the same database transaction as its block.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RowInsert {
+    OilToken(Box<OilTokenModel>),
+    Tokenization(Box<TokenizationModel>),
+    Comment(Box<CommentModel>),
+}
+
+impl RowInsert {
+    async fn insert<C: ConnectionTrait>(self, db: &C) -> Result<(), DbErr> {
+        // every column is written, the id included
+        match self {
+            RowInsert::OilToken(row) => {
+                oil_token::ActiveModel::from(*row).reset_all().insert(db).await?;
+            }
+            RowInsert::Tokenization(row) => {
+                tokenization::ActiveModel::from(*row).reset_all().insert(db).await?;
+            }
+            RowInsert::Comment(row) => {
+                comment::ActiveModel::from(*row).reset_all().insert(db).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Input of a transition job: the actions it proves and the row written with its block.
+#[derive(Serialize, Deserialize)]
+pub struct TransitionJob {
+    pub actor: Actor,
+    pub actions: Vec<Transition>,
+    pub row: RowInsert,
+}
+
+// Queues the proof of `transition` and returns the job id. The transition is checked
+// against the chain head now, the worker that proves it stages it again on the head it
+// finds and stores the block and the row, see prove_queued.
+pub async fn queue_transition(
+    db: &DatabaseConnection,
+    transition: StateTransition,
+    actor: Actor,
+    row: RowInsert
+) -> Result<i32, ApiError> {
+    let params = head_params(db, transition, actor).await?;
+    let input = json!(TransitionJob { actor: params.actor, actions: params.actions, row });
+    Ok(enqueue_job(db, JobKind::Transition, Some(input), ReceiptKind::default()).await?)
+}
+
+// Proves a queued transition on top of the current chain head and stores it. Writes queued
+// on the same head land one after the other, each staged on the block of the one before.
+pub async fn prove_queued(
+    db: &DatabaseConnection,
+    prover: SharedProver,
+    job: TransitionJob
+) -> Result<(TransitionCommit, BlockHeader), ApiError> {
+    let mut transition = StateTransition::load(db).await?;
+    transition.restage(job.actions).map_err(ApiError::Validation)?;
+    let params = head_params(db, transition, job.actor).await?;
+    let transition_commit = tokio::task
+        ::spawn_blocking(move || prove_transition(&*prover, params))
+        .await?
+        .map_err(ApiError::Prover)?;
+    let block = store_transition(db, &transition_commit, job.row).await?;
+    Ok((transition_commit, block))
+}
+
+// Stores a proven transition as the next block together with the row it creates. Both are
+// written or neither is, and a block racing another one onto the same parent is refused,
+// so the table never moves away from the chain.
+pub async fn store_transition(
+    db: &DatabaseConnection,
+    transition_commit: &TransitionCommit,
+    row: RowInsert
+) -> Result<BlockHeader, ApiError> {
+    let state = transition_commit
+        .get_commit()
+        .map_err(ApiError::Internal)?;
+
+    let txn = db.begin().await?;
+    store_block(&txn, BlockKind::Transition, &state.block, &transition_commit.receipt).await?;
+    row.insert(&txn).await?;
+    txn.commit().await?;
+    Ok(state.block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oil_tokenization_core::{ LeafEncoder, StateTable };
+
+    fn leaf(id: i32) -> Leaf {
+        LeafEncoder::new(StateTable::Comment).i32(id).str("comment").finish()
+    }
+
+    fn rows(ids: &[i32]) -> Vec<(i32, Leaf)> {
+        ids.iter()
+            .map(|id| (*id, leaf(*id)))
+            .collect()
+    }
+
+    #[test]
+    fn create_delete_create_keeps_host_and_guest_roots_equal() {
+        let mut host = StagedTable::new(&rows(&[1, 2, 3])).unwrap();
+        let mut guest = host.root();
+        let mut stored = vec![1, 2, 3];
+
+        let steps = [(4, Some(leaf(4))), (2, None), (5, Some(leaf(5))), (5, None), (6, Some(leaf(6)))];
+        for (id, after) in steps {
+            let new_leaf = after
+                .as_ref()
+                .map(|leaf| hash_leaf(leaf))
+                .unwrap_or(Digest::ZERO);
+            let (proof, old_leaf) = host.stage(id, after.clone()).unwrap();
+            // a delete carries one path like any other change
+            assert_eq!(proof.siblings.len(), oil_tokenization_core::merkle::TREE_DEPTH);
+            guest.apply(&proof, &old_leaf, &new_leaf).unwrap();
+            match after {
+                Some(_) => stored.push(id),
+                None => stored.retain(|stored| *stored != id),
+            }
+            // the table the host rebuilds from the stored rows after every commit
+            assert_eq!(guest, host.root());
+            assert_eq!(guest, StagedTable::new(&rows(&stored)).unwrap().root());
+        }
+        assert_eq!(
+            host
+                .rows()
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>(),
+            [1, 3, 4, 6]
+        );
+    }
+
+    fn comment(id: i32) -> CommentRecord {
+        CommentRecord {
+            id,
+            oil_token_id: 1,
+            user: "0x01".to_string(),
+            content: "comment".to_string(),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    // the actions a create request queues, staged on the head it was sent on
+    fn queued_create(stored: &[CommentRecord], id: i32) -> Vec<Transition> {
+        let mut transition = StateTransition::new(&[], &[], &comment_rows(stored)).unwrap();
+        transition.comment(CommentAction::CREATE, None, Some(comment(id))).unwrap();
+        let job = json!(transition.actions);
+        serde_json::from_value(job).unwrap()
+    }
+
+    fn comment_rows(stored: &[CommentRecord]) -> Vec<(i32, Leaf)> {
+        stored
+            .iter()
+            .map(|r| (r.id, r.leaf()))
+            .collect()
+    }
+
+    // the worker stages the job again on the stored rows and the head it finds
+    fn land(stored: &mut Vec<CommentRecord>, head: BlockHeader, actions: Vec<Transition>) -> BlockHeader {
+        let mut transition = StateTransition::new(&[], &[], &comment_rows(stored)).unwrap();
+        transition.restage(actions.clone()).unwrap();
+        let state = transition.params(head, Actor::SYSTEM).apply().unwrap();
+        if let Some(Transition::Comment { after: Some(row), .. }) = actions.into_iter().next() {
+            stored.push(*row);
+        }
+        state.block
+    }
+
+    #[test]
+    fn writes_queued_on_the_same_head_both_land() {
+        let mut stored = vec![comment(1)];
+        let genesis = StateTransition::new(&[], &[], &comment_rows(&stored)).unwrap();
+        let genesis = BlockHeader::genesis(genesis.roots.root(), 0);
+
+        // both requests are staged on the genesis block before either is proven
+        let first = queued_create(&stored, 2);
+        let second = queued_create(&stored, 3);
+
+        let head = land(&mut stored, genesis, first);
+        // proven with the paths it was queued with, the second write misses the new head
+        let mut queued = StateTransition::new(&[], &[], &comment_rows(&stored[..1])).unwrap();
+        queued.actions = second.clone();
+        assert!(queued.params(head, Actor::SYSTEM).apply().is_err());
+
+        let head = land(&mut stored, head, second);
+        assert_eq!(head.height, 2);
+        assert_eq!(head.root, StateTransition::new(&[], &[], &comment_rows(&stored)).unwrap().roots.root());
+    }
+
+    #[test]
+    fn restaging_a_row_already_stored_is_refused() {
+        let stored = vec![comment(1), comment(2)];
+        let actions = queued_create(&stored[..1], 2);
+        let mut transition = StateTransition::new(&[], &[], &comment_rows(&stored)).unwrap();
+        assert!(transition.restage(actions).is_err());
+    }
+
+    #[test]
+    fn deleting_an_unknown_row_is_refused() {
+        let mut host = StagedTable::new(&rows(&[1])).unwrap();
+        assert!(host.stage(2, None).is_err());
+        assert!(host.stage(0, Some(leaf(0))).is_err());
+        assert!(StagedTable::new(&rows(&[1, 1])).is_err());
+    }
+}
@@ -1,5 +1,3 @@
-#![no_std]
-
 use core::fmt::{ Debug, Formatter, Result as FmtResult };
 
 pub struct MiniVec<T, const N: usize> {
@@ -8,7 +6,7 @@ pub struct MiniVec<T, const N: usize> {
 }
 
 impl<T: Copy + Default, const N: usize> MiniVec<T, N> {
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             data: [T::default(); N],
             len: 0,
@@ -32,11 +30,21 @@ impl<T: Copy + Default, const N: usize> MiniVec<T, N> {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn as_slice(&self) -> &[T] {
         &self.data[..self.len]
     }
 }
 
+impl<T: Copy + Default, const N: usize> Default for MiniVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Debug, const N: usize> Debug for MiniVec<T, N> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         f.debug_list()
@@ -44,3 +52,28 @@ impl<T: Debug, const N: usize> Debug for MiniVec<T, N> {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_past_capacity_fails() {
+        let mut values: MiniVec<u8, 2> = MiniVec::default();
+        assert!(values.is_empty());
+        values.push(1).unwrap();
+        values.push(2).unwrap();
+        assert_eq!(values.push(3), Err("MiniVec is full"));
+        assert_eq!(values.as_slice(), &[1, 2]);
+    }
+
+    #[test]
+    fn get_out_of_range_is_none() {
+        let mut values: MiniVec<u8, 4> = MiniVec::new();
+        values.push(7).unwrap();
+        assert_eq!(values.get(0), Some(7));
+        // slots past len hold defaults but are not readable
+        assert_eq!(values.get(1), None);
+        assert_eq!(values.get(4), None);
+    }
+}
@@ -0,0 +1,75 @@
+use alloc::string::{ String, ToString };
+use serde::{ Deserialize, Serialize };
+use risc0_zkp::core::digest::Digest;
+
+use crate::chain::{ check_timestamp, StateLink };
+
+// Header of one proven state in the chain of state blocks. The genesis block is the first
+// full proof, at height 0 with an all-zero parent. Every later block names the root it continues from.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlockHeader {
+    pub height: u64,
+    pub parent_root: Digest,
+    pub root: Digest,
+    pub previous_updated_at: i64,
+    pub updated_at: i64,
+}
+
+impl BlockHeader {
+    pub fn genesis(root: Digest, updated_at: i64) -> Self {
+        BlockHeader {
+            height: 0,
+            parent_root: Digest::ZERO,
+            root,
+            previous_updated_at: updated_at,
+            updated_at,
+        }
+    }
+
+    /// Header of the block proving `link` on top of this one. Refuses a link that
+    /// does not start from this root and timestamp.
+    pub fn child(&self, link: &StateLink) -> Result<BlockHeader, String> {
+        if link.old_state != self.root {
+            return Err("State does not continue from the parent block".to_string());
+        }
+        if link.previous_updated_at != self.updated_at {
+            return Err("Previous timestamp does not match the parent block".to_string());
+        }
+        check_timestamp(link.previous_updated_at, link.updated_at)?;
+
+        Ok(BlockHeader {
+            height: self.height + 1,
+            parent_root: self.root,
+            root: link.new_state,
+            previous_updated_at: link.previous_updated_at,
+            updated_at: link.updated_at,
+        })
+    }
+
+    pub fn follows(&self, parent: &BlockHeader) -> Result<(), String> {
+        if self.height != parent.height + 1 {
+            return Err("Block height does not follow its parent".to_string());
+        }
+        if self.parent_root != parent.root {
+            return Err("Parent root does not match the previous block".to_string());
+        }
+        if self.previous_updated_at != parent.updated_at {
+            return Err("Previous timestamp does not match the previous block".to_string());
+        }
+        check_timestamp(self.previous_updated_at, self.updated_at)
+    }
+}
+
+/// Checks that `blocks` start at genesis and that each one continues from the one before it.
+pub fn verify_blocks(blocks: &[BlockHeader]) -> Result<(), String> {
+    let Some(genesis) = blocks.first() else {
+        return Err("No genesis block".to_string());
+    };
+    if genesis.height != 0 || genesis.parent_root != Digest::ZERO {
+        return Err("First block is not a genesis block".to_string());
+    }
+    for pair in blocks.windows(2) {
+        pair[1].follows(&pair[0])?;
+    }
+    Ok(())
+}
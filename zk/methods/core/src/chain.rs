@@ -0,0 +1,45 @@
+use alloc::string::{ String, ToString };
+use serde::{ Deserialize, Serialize };
+use risc0_zkp::core::digest::Digest;
+
+// Time is an input of every guest, never read inside the zkVM. The host supplies a unix
+// timestamp (or a block height) and the guest commits it next to the previous one it continues from.
+
+pub fn check_timestamp(previous_updated_at: i64, updated_at: i64) -> Result<(), String> {
+    if updated_at < previous_updated_at {
+        return Err("Timestamp is earlier than the previous state".to_string());
+    }
+    Ok(())
+}
+
+// One step `old_state -> new_state` of the committed state, as found in any state journal.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StateLink {
+    pub old_state: Digest,
+    pub new_state: Digest,
+    pub previous_updated_at: i64,
+    pub updated_at: i64,
+}
+
+impl StateLink {
+    pub fn follows(&self, previous: &StateLink) -> Result<(), String> {
+        if self.old_state != previous.new_state {
+            return Err("State does not continue from the previous state".to_string());
+        }
+        if self.previous_updated_at != previous.updated_at {
+            return Err("Previous timestamp does not match the previous state".to_string());
+        }
+        check_timestamp(self.previous_updated_at, self.updated_at)
+    }
+}
+
+/// Checks that every link continues from the one before it, with non-decreasing timestamps.
+pub fn verify_chain(links: &[StateLink]) -> Result<(), String> {
+    for link in links {
+        check_timestamp(link.previous_updated_at, link.updated_at)?;
+    }
+    for pair in links.windows(2) {
+        pair[1].follows(&pair[0])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use alloc::{ format, string::String, vec::Vec };
+use serde::{ Deserialize, Serialize };
+use risc0_zkp::core::digest::Digest;
+
+use crate::block::BlockHeader;
+use crate::chain::StateLink;
+use crate::transition::{ leaf_hash, TableRoots };
+use crate::{ leaf_id, slot, Leaf, MerkleProof, StateTable };
+
+// A changed leaf since the last synced state. Only the hash of the old value is needed,
+// its content was already committed by the previous proof.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LeafUpdate {
+    pub table: StateTable,
+    pub proof: MerkleProof,
+    pub old_leaf: Digest,
+    pub new_leaf: Option<Leaf>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DeltaParams {
+    pub parent: BlockHeader,
+    pub timestamp: i64,
+    pub roots: TableRoots,
+    pub updates: Vec<LeafUpdate>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DeltaState {
+    pub link: StateLink,
+    pub block: BlockHeader,
+    pub roots: TableRoots,
+    pub updates: u32,
+}
+
+impl DeltaParams {
+    /// Moves the previous roots forward by the changed leaves only, O(updates * TREE_DEPTH)
+    /// hashes whatever the size of the tables.
+    pub fn apply(self) -> Result<DeltaState, String> {
+        let (link, block, roots) = self.roots.advance(&self.parent, self.timestamp, |roots| {
+            for update in &self.updates {
+                if let Some(leaf) = &update.new_leaf {
+                    if leaf_id(update.table, leaf).and_then(slot) != Some(update.proof.index) {
+                        return Err(format!("{:?} leaf is not at its slot", update.table));
+                    }
+                }
+                roots
+                    .table_mut(update.table)
+                    .apply(&update.proof, &update.old_leaf, &leaf_hash(update.new_leaf.clone()))?;
+            }
+            Ok(())
+        })?;
+
+        Ok(DeltaState {
+            link,
+            block,
+            roots,
+            updates: self.updates.len() as u32,
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use alloc::string::ToString;
+
+use crate::{ MarketAction, SiweMessage, StateTable };
+
+// Login and market action the EIP-712 and SIWE tests of this crate and of the host sign and
+// check, with the well known development wallet below. Its key is never funded.
+pub const WALLET: &str = "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+pub const WALLET_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+// 2025-01-01T12:00:00Z
+pub const NOW: i64 = 1735732800;
+
+// Login of WALLET issued at NOW and valid for five minutes.
+pub fn siwe_message() -> SiweMessage {
+    SiweMessage {
+        domain: "localhost:3001".to_string(),
+        address: "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266".to_string(),
+        statement: Some("Sign in to the Oil Tokenization Platform".to_string()),
+        uri: "http://localhost:3001".to_string(),
+        version: "1".to_string(),
+        chain_id: 1,
+        nonce: "abcdefgh12345678".to_string(),
+        issued_at: NOW,
+        expiration_time: Some(NOW + 300),
+        not_before: None,
+    }
+}
+
+// Comment created by WALLET, signed until five minutes after NOW.
+pub fn market_action() -> MarketAction {
+    MarketAction {
+        actor: WALLET.to_string(),
+        table: StateTable::Comment,
+        action: "CREATE".to_string(),
+        content: MarketAction::content_hash(br#"{"content":"hello"}"#),
+        nonce: "nonce12345678".to_string(),
+        deadline: NOW + 300,
+    }
+}
@@ -0,0 +1,202 @@
+#[cfg(feature = "std")]
+use alloc::string::{ String, ToString };
+use serde::{ Deserialize, Serialize };
+use risc0_zkp::core::digest::Digest;
+#[cfg(feature = "std")]
+use risc0_zkvm::Receipt;
+
+use crate::{ leaf_id, slot, BlockHeader, Leaf, MerkleProof, OverallState, TableRoots };
+#[cfg(feature = "std")]
+use crate::{ DeltaState, TransitionState };
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum StateTable {
+    OilToken,
+    Tokenization,
+    Comment,
+}
+
+// proof that a single record is part of the state committed by the block at `height`.
+// leaf -> sub_state_root (merkle path from the slot of its id) -> overall_root (keccak over the three sub roots)
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct InclusionProof {
+    pub table: StateTable,
+    pub leaf: Leaf,
+    pub proof: MerkleProof,
+    pub sub_state_root: Digest,
+    pub overall_root: Digest,
+    pub height: u64,
+}
+
+impl InclusionProof {
+    /// Checks the proof against the table roots committed by `block`.
+    pub fn verify_block(&self, block: &BlockHeader, roots: &TableRoots) -> bool {
+        roots.root() == block.root &&
+            self.height == block.height &&
+            self.overall_root == block.root &&
+            self.sub_state_root == roots.table(self.table).root &&
+            leaf_id(self.table, &self.leaf).and_then(slot) == Some(self.proof.index) &&
+            self.proof.verify(&self.sub_state_root, &self.leaf)
+    }
+
+    /// Checks the proof against a decoded `OverallState` journal.
+    pub fn verify(&self, state: &OverallState) -> bool {
+        state.new_state == state.block.root && self.verify_block(&state.block, &state.roots())
+    }
+
+    /// Verifies a block receipt of any guest that proves blocks against `image`, then checks
+    /// the proof against the roots its journal commits. Dev-mode receipts are refused unless
+    /// `accept_fake` is set, as the host does without ACCEPT_DEV_RECEIPTS.
+    #[cfg(feature = "std")]
+    pub fn verify_receipt(
+        &self,
+        receipt: &Receipt,
+        image: BlockImage,
+        accept_fake: bool
+    ) -> Result<BlockHeader, String> {
+        crate::verify_receipt(receipt, image.image_id(), accept_fake)?;
+        let (block, roots) = image.decode(receipt)?;
+        if !self.verify_block(&block, &roots) {
+            return Err("Record is not included in the committed state".to_string());
+        }
+        Ok(block)
+    }
+}
+
+/// Image a block receipt was proven with, which decides how its journal is decoded.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockImage {
+    /// `init_overall`, whose sub-states were proven with `state_image_id`.
+    Overall {
+        image_id: Digest,
+        state_image_id: Digest,
+    },
+    Transition(Digest),
+    Delta(Digest),
+}
+
+#[cfg(feature = "std")]
+impl BlockImage {
+    pub fn image_id(&self) -> Digest {
+        match self {
+            BlockImage::Overall { image_id, .. } => *image_id,
+            BlockImage::Transition(image_id) | BlockImage::Delta(image_id) => *image_id,
+        }
+    }
+
+    /// Decodes the block a receipt of this image commits and the roots of its three tables.
+    /// The receipt itself is not verified here.
+    pub fn decode(&self, receipt: &Receipt) -> Result<(BlockHeader, TableRoots), String> {
+        match self {
+            BlockImage::Overall { state_image_id, .. } => {
+                let (committed_image_id, state): (Digest, OverallState) = receipt.journal
+                    .decode()
+                    .map_err(|e| e.to_string())?;
+                if committed_image_id != *state_image_id {
+                    return Err("Sub-states were proven with another image".to_string());
+                }
+                if state.new_state != state.block.root {
+                    return Err("State does not match its block".to_string());
+                }
+                Ok((state.block, state.roots()))
+            }
+            BlockImage::Transition(_) => {
+                let state: TransitionState = receipt.journal.decode().map_err(|e| e.to_string())?;
+                Ok((state.block, state.roots))
+            }
+            BlockImage::Delta(_) => {
+                let state: DeltaState = receipt.journal.decode().map_err(|e| e.to_string())?;
+                Ok((state.block, state.roots))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ LeafEncoder, MerkleTree, SubStateRoot };
+    use alloc::{ vec, vec::Vec };
+
+    fn leaves(table: StateTable, ids: &[i32]) -> Vec<Leaf> {
+        ids.iter()
+            .map(|id| LeafEncoder::new(table).i32(*id).str("row").finish())
+            .collect()
+    }
+
+    fn sub_root(leaves: &[Leaf]) -> SubStateRoot {
+        SubStateRoot { root: MerkleTree::new(leaves).root(), leaves: leaves.len() as u64 }
+    }
+
+    // a block on top of genesis, as a transition or delta commits it
+    fn block(roots: &TableRoots) -> BlockHeader {
+        BlockHeader {
+            height: 1,
+            parent_root: Digest::ZERO,
+            root: roots.root(),
+            previous_updated_at: 0,
+            updated_at: 1,
+        }
+    }
+
+    fn proof(table: StateTable, rows: &[Leaf], index: usize, block: &BlockHeader) -> InclusionProof {
+        let tree = MerkleTree::new(rows);
+        InclusionProof {
+            table,
+            leaf: rows[index].clone(),
+            proof: tree.proof(index).unwrap(),
+            sub_state_root: tree.root(),
+            overall_root: block.root,
+            height: block.height,
+        }
+    }
+
+    #[test]
+    fn proof_verifies_against_block_roots() {
+        let oil_tokens = leaves(StateTable::OilToken, &[1, 2]);
+        let comments = leaves(StateTable::Comment, &[1, 2, 3]);
+        let roots = TableRoots {
+            oil_token: sub_root(&oil_tokens),
+            tokenization: sub_root(&[]),
+            comment: sub_root(&comments),
+        };
+        let block = block(&roots);
+        let proof = proof(StateTable::Comment, &comments, 1, &block);
+        assert!(proof.verify_block(&block, &roots));
+
+        let mut other_table = proof.clone();
+        other_table.table = StateTable::OilToken;
+        assert!(!other_table.verify_block(&block, &roots));
+
+        let mut other_height = proof.clone();
+        other_height.height = 2;
+        assert!(!other_height.verify_block(&block, &roots));
+
+        let mut other_leaf = proof;
+        other_leaf.leaf = comments[2].clone();
+        assert!(!other_leaf.verify_block(&block, &roots));
+    }
+
+    #[test]
+    fn proof_at_the_slot_of_another_id_is_refused() {
+        // a tree holding the record 2 in the slot of record 1 as well
+        let comments = leaves(StateTable::Comment, &[2, 2]);
+        let roots = TableRoots {
+            oil_token: sub_root(&[]),
+            tokenization: sub_root(&[]),
+            comment: sub_root(&comments),
+        };
+        let block = block(&roots);
+        assert!(proof(StateTable::Comment, &comments, 1, &block).verify_block(&block, &roots));
+        assert!(!proof(StateTable::Comment, &comments, 0, &block).verify_block(&block, &roots));
+    }
+
+    #[test]
+    fn proof_verifies_against_overall_state() {
+        let oil_tokens = leaves(StateTable::OilToken, &[1, 2, 3]);
+        let state = OverallState::init(oil_tokens.clone(), vec![], vec![], 0);
+        let proof = proof(StateTable::OilToken, &oil_tokens, 2, &state.block);
+        assert!(proof.verify(&state));
+    }
+}
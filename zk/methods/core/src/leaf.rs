@@ -0,0 +1,108 @@
+use alloc::{ format, string::String, vec, vec::Vec };
+
+use crate::StateTable;
+
+// Canonical binary encoding of a committed row, the bytes hashed into its Merkle leaf.
+//
+//   leaf    = version (u8) || table (u8) || field*
+//   integer = big-endian, fixed width (i32 -> 4 bytes, i64 -> 8 bytes)
+//   string  = length (u32, big-endian) || utf-8 bytes
+//   option  = 0x00 | 0x01 || value
+//
+// Fields are written in the order of the record struct. Decimals are strings in their
+// normalized form (no trailing zeros, no "-0") and JSON columns are compact with sorted keys,
+// so the same data always gives the same bytes. Bump LEAF_VERSION on any change to a layout.
+
+pub const LEAF_VERSION: u8 = 1;
+
+// Encoded bytes of one row.
+pub type Leaf = Vec<u8>;
+
+impl StateTable {
+    pub fn tag(&self) -> u8 {
+        match self {
+            StateTable::OilToken => 1,
+            StateTable::Tokenization => 2,
+            StateTable::Comment => 3,
+        }
+    }
+}
+
+// Primary key a leaf of `table` was encoded with, the first field of every record.
+// None when the leaf is not a current leaf of that table.
+pub fn leaf_id(table: StateTable, leaf: &[u8]) -> Option<i32> {
+    match leaf {
+        [LEAF_VERSION, tag, a, b, c, d, ..] if *tag == table.tag() => {
+            Some(i32::from_be_bytes([*a, *b, *c, *d]))
+        }
+        _ => None,
+    }
+}
+
+// Slot of the row `id` in its table tree. Every row stays at its slot, its primary key
+// minus one, and the slots of missing or deleted rows hold the empty leaf, so a change
+// never moves another row.
+pub fn slot(id: i32) -> Option<u64> {
+    u64::try_from(id).ok()?.checked_sub(1)
+}
+
+// Leaves of a table laid out at their slots, an empty leaf in every free slot.
+pub fn slot_leaves(rows: &[(i32, Leaf)]) -> Result<Vec<Leaf>, String> {
+    let mut leaves: Vec<Leaf> = vec![];
+    for (id, leaf) in rows {
+        let index = slot(*id).ok_or_else(|| format!("Record {} has no slot", id))? as usize;
+        if index >= leaves.len() {
+            leaves.resize(index + 1, vec![]);
+        }
+        if !leaves[index].is_empty() {
+            return Err(format!("Record {} is stored twice", id));
+        }
+        leaves[index] = leaf.clone();
+    }
+    Ok(leaves)
+}
+
+pub struct LeafEncoder {
+    buf: Vec<u8>,
+}
+
+impl LeafEncoder {
+    pub fn new(table: StateTable) -> Self {
+        LeafEncoder {
+            buf: vec![LEAF_VERSION, table.tag()],
+        }
+    }
+
+    pub fn i32(mut self, value: i32) -> Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn i64(mut self, value: i64) -> Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn str(mut self, value: &str) -> Self {
+        self.buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        self.buf.extend_from_slice(value.as_bytes());
+        self
+    }
+
+    pub fn opt_str(mut self, value: Option<&str>) -> Self {
+        match value {
+            Some(value) => {
+                self.buf.push(1);
+                self.str(value)
+            }
+            None => {
+                self.buf.push(0);
+                self
+            }
+        }
+    }
+
+    pub fn finish(self) -> Leaf {
+        self.buf
+    }
+}
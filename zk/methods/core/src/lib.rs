@@ -1,14 +1,80 @@
-// #![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+use alloc::{ format, string::{ String, ToString }, vec, vec::Vec };
 use serde::{ Deserialize, Serialize };
 use risc0_zkp::core::digest::Digest;
 use tiny_keccak::{ Hasher, Keccak };
-use chrono::{ DateTime, Local };
+use rust_decimal::Decimal;
+
+pub mod array;
+pub mod block;
+pub mod merkle;
+pub mod chain;
+pub mod leaf;
+pub mod inclusion;
+pub mod record;
+pub mod transition;
+pub mod delta;
+pub mod sparse;
+pub mod state;
+pub mod signature;
+pub mod eip712;
+pub mod siwe;
+#[cfg(feature = "std")]
+pub mod receipt;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
+
+pub use array::MiniVec;
+pub use block::{ verify_blocks, BlockHeader };
+pub use merkle::{ MerkleProof, MerkleTree };
+pub use chain::{ check_timestamp, verify_chain, StateLink };
+pub use leaf::{ leaf_id, slot, slot_leaves, Leaf, LeafEncoder, LEAF_VERSION };
+#[cfg(feature = "std")]
+pub use inclusion::BlockImage;
+#[cfg(feature = "std")]
+pub use receipt::verify_receipt;
+pub use inclusion::{ InclusionProof, StateTable };
+pub use record::{ CommentRecord, OilTokenRecord, Record, TokenizationRecord };
+pub use transition::{
+    SubStateRoot,
+    TableRoots,
+    Transition,
+    TransitionState,
+    TransitionParams,
+};
+pub use delta::{ DeltaParams, DeltaState, LeafUpdate };
+pub use sparse::{ KeyedParams, KeyedProof, KeyedState, SparseMerkleTree };
+pub use state::{ Action, SubState, SubStateParams };
+pub use signature::{
+    address_hex,
+    eip191_hash,
+    recover_ethereum_address,
+    recover_prehash,
+    SignatureScheme,
+};
+pub use eip712::{
+    recover_typed_signer,
+    signing_hash,
+    Eip712Domain,
+    Login,
+    MarketAction,
+    TypedData,
+    TypedWords,
+};
+pub use siwe::{ format_rfc3339, parse_rfc3339, SiweMessage };
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct VerifyParams {
+    // SIWE (EIP-4361) message signed by the wallet
     pub message: String,
     pub signature_bytes: String,
+    pub scheme: SignatureScheme,
     pub expected_addr: String,
+    // domain and chain the login must be scoped to
+    pub domain: String,
+    pub chain_id: u64,
     pub timestamp: i64,
     pub username: String,
 }
@@ -19,45 +85,22 @@ pub struct VerifyCommit {
     pub address: String,
     pub timestamp: i64,
     pub username: String,
+    pub scheme: SignatureScheme,
+    // parsed login message, None when it is not a valid SIWE message
+    pub message: Option<SiweMessage>,
 }
 
+// Input of the init_overall guest. Each sub-state comes with an init_state receipt,
+// proven with `state_image_id`, that the guest takes as an assumption. Without a parent
+// block the proof is the genesis of the chain, otherwise it continues from `parent`.
 #[derive(Serialize, Deserialize)]
 pub struct OverallParams {
-    pub oil_token_leaves: Vec<String>,
-    pub tokenization_leaves: Vec<String>,
-    pub comment_leaves: Vec<String>,
-}
-
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct OilTokenizationState {
-    pub oil_token_state: Digest,
-    pub tokenization_state: Digest,
-    pub comment_state: Digest,
-    pub overall: Digest,
-}
-
-// tokenization commit :: CRUD
-// comment commit :: CRUD
-// oil_token commit :: CRUD
-pub enum OilTokenizationAction {}
-impl OilTokenizationState {
-    pub fn init() {
-        // start of the database
-        // stores necessay details to communicate with smart contract.
-        // stores initial state on the blockchain
-        // initial state serves as the start of the merkle tree on the blockchain (oil_token, tokenization, comment, overall)
-        // cannot reset state or reinit state.
-    }
-
-    pub fn process() {
-        // process results in change of state
-        // update state and sync with remote state.
-    }
-
-    fn sync() {
-        //sync state with smart contract
-        //verify localState with remoteState (contract)
-    }
+    pub state_image_id: Digest,
+    pub oil_token_state: OilTokenState,
+    pub tokenization_state: TokenizationState,
+    pub comment_state: CommentState,
+    pub parent: Option<BlockHeader>,
+    pub timestamp: i64,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -77,61 +120,48 @@ pub enum Actor {
 // actor rules - clearly stating what those actors does and their permissions.
 // allow for transparency on who issued commands without revealing the actors identity.
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct TokenizationState {
-    pub old_state: Vec<Digest>,
-    pub new_state: Vec<Digest>,
-    pub overall: Digest,
-    pub leaves: usize,
-    pub action: TokenizationAction,
-    pub actor: Actor,
-}
+pub type TokenizationState = SubState<TokenizationAction>;
 
-// verify actor before completing a process.
-impl TokenizationState {
-    pub fn init(db: Vec<String>) -> Self {
-        let mut tokenization_state: Vec<Digest> = vec![];
-        let mut hasher = Keccak::v256();
-        let mut hasherb = Keccak::v256();
-        for x in &db {
-            hasher.update(&x.as_bytes());
-            hasherb.update(&x.as_bytes());
-            let mut output = [0; 32];
-            hasher.clone().finalize(&mut output);
-            let digest = Digest::from_bytes(output);
-            tokenization_state.push(digest);
-        }
-        let mut output = [0; 32];
-        hasherb.finalize(&mut output);
-        let digest = Digest::from_bytes(output);
-
-        TokenizationState {
-            old_state: tokenization_state.clone(),
-            new_state: tokenization_state,
-            overall: digest,
-            leaves: db.len(),
-            action: TokenizationAction::INIT,
-            actor: Actor::SYSTEM,
-        }
-    }
+impl Action for TokenizationAction {
+    type Record = TokenizationRecord;
 
-    pub fn update(&mut self, new_state: Vec<Digest>, overall: Digest) {
-        self.old_state = self.new_state.clone();
-        self.new_state = new_state;
-        self.overall = overall;
-    }
+    const TABLE: StateTable = StateTable::Tokenization;
+    const INIT: Self = TokenizationAction::INIT;
 
-    pub fn process(action: TokenizationAction) {
-        match action {
-            TokenizationAction::INIT => {
-                // call the function here and make sure it workes.
-                // each function call returns the new state which will be used here to update the state.
+    // business rules of a single tokenization mutation, checked before its leaf is swapped.
+    fn process(
+        &self,
+        before: Option<&TokenizationRecord>,
+        after: Option<&TokenizationRecord>
+    ) -> Result<(), String> {
+        match (self, before, after) {
+            (TokenizationAction::CREATE, None, Some(new)) => {
+                if new.amount()? <= Decimal::ZERO {
+                    return Err("Tokenization amount must be positive".to_string());
+                }
+                Ok(())
             }
-            _ => {}
+            (TokenizationAction::UPDATE, Some(old), Some(new)) => {
+                if
+                    new.id != old.id ||
+                    new.oil_token_id != old.oil_token_id ||
+                    new.tokenizer_id != old.tokenizer_id ||
+                    new.created_at != old.created_at
+                {
+                    return Err("Tokenization identity cannot change".to_string());
+                }
+                if new.updated_at < old.updated_at {
+                    return Err("updated_at cannot go backwards".to_string());
+                }
+                if new.amount()? <= Decimal::ZERO {
+                    return Err("Tokenization amount must be positive".to_string());
+                }
+                Ok(())
+            }
+            (TokenizationAction::DELETE, Some(_), None) => Ok(()),
+            _ => Err(format!("Invalid tokenization transition {:?}", self)),
         }
     }
-
-    pub fn sync() {}
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -142,60 +172,48 @@ pub enum CommentAction {
     DELETE,
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct CommentState {
-    pub old_state: Vec<Digest>,
-    pub new_state: Vec<Digest>,
-    pub overall: Digest,
-    pub leaves: usize,
-    pub action: CommentAction,
-    pub actor: Actor,
-}
+pub type CommentState = SubState<CommentAction>;
 
-impl CommentState {
-    pub fn init(db: Vec<String>) -> Self {
-        let mut comment_state: Vec<Digest> = vec![];
-        let mut hasher = Keccak::v256();
-        let mut hasherb = Keccak::v256();
-        for x in &db {
-            hasher.update(&x.as_bytes());
-            hasherb.update(&x.as_bytes());
-            let mut output = [0; 32];
-            hasher.clone().finalize(&mut output);
-            let digest = Digest::from_bytes(output);
-            comment_state.push(digest);
-        }
-        let mut output = [0; 32];
-        hasherb.finalize(&mut output);
-        let digest = Digest::from_bytes(output);
-
-        CommentState {
-            old_state: comment_state.clone(),
-            new_state: comment_state,
-            overall: digest,
-            leaves: db.len(),
-            action: CommentAction::INIT,
-            actor: Actor::SYSTEM,
-        }
-    }
+impl Action for CommentAction {
+    type Record = CommentRecord;
 
-    pub fn update(&mut self, new_state: Vec<Digest>, overall: Digest) {
-        self.old_state = self.new_state.clone();
-        self.new_state = new_state;
-        self.overall = overall;
-    }
+    const TABLE: StateTable = StateTable::Comment;
+    const INIT: Self = CommentAction::INIT;
 
-    pub fn process(action: CommentAction) {
-        match action {
-            CommentAction::INIT => {
-                // call the function here and make sure it workes.
-                // each function call returns the new state which will be used here to update the state.
+    // business rules of a single comment mutation, checked before its leaf is swapped.
+    fn process(
+        &self,
+        before: Option<&CommentRecord>,
+        after: Option<&CommentRecord>
+    ) -> Result<(), String> {
+        match (self, before, after) {
+            (CommentAction::CREATE, None, Some(new)) => {
+                if new.content.trim().is_empty() {
+                    return Err("Comment cannot be empty".to_string());
+                }
+                Ok(())
+            }
+            (CommentAction::UPDATE, Some(old), Some(new)) => {
+                if
+                    new.id != old.id ||
+                    new.oil_token_id != old.oil_token_id ||
+                    new.user != old.user ||
+                    new.created_at != old.created_at
+                {
+                    return Err("Comment identity cannot change".to_string());
+                }
+                if new.updated_at < old.updated_at {
+                    return Err("updated_at cannot go backwards".to_string());
+                }
+                if new.content.trim().is_empty() {
+                    return Err("Comment cannot be empty".to_string());
+                }
+                Ok(())
             }
-            _ => {}
+            (CommentAction::DELETE, Some(_), None) => Ok(()),
+            _ => Err(format!("Invalid comment transition {:?}", self)),
         }
     }
-
-    pub fn sync() {}
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -205,37 +223,98 @@ pub struct OverallState {
     pub comment_state: CommentState,
     pub old_state: Digest,
     pub new_state: Digest,
-    pub updated_at: String,
+    pub previous_updated_at: i64,
+    pub updated_at: i64,
+    pub block: BlockHeader,
 }
 
 impl OverallState {
-    pub fn new() -> Self {
+    /// Keccak commitment over the three sub-state Merkle roots.
+    pub fn compute_root(oil_token: &Digest, tokenization: &Digest, comment: &Digest) -> Digest {
+        let mut hasher = Keccak::v256();
+        hasher.update(oil_token.as_bytes());
+        hasher.update(tokenization.as_bytes());
+        hasher.update(comment.as_bytes());
+        let mut output = [0; 32];
+        hasher.finalize(&mut output);
+        Digest::from_bytes(output)
+    }
+
+    pub fn sub_state_root(&self, table: StateTable) -> Digest {
+        match table {
+            StateTable::OilToken => self.oil_token_state.new_state,
+            StateTable::Tokenization => self.tokenization_state.new_state,
+            StateTable::Comment => self.comment_state.new_state,
+        }
+    }
+
+    // roots of the three sub-states, as transitions and deltas commit them.
+    pub fn roots(&self) -> TableRoots {
+        TableRoots {
+            oil_token: SubStateRoot {
+                root: self.oil_token_state.new_state,
+                leaves: self.oil_token_state.leaves,
+            },
+            tokenization: SubStateRoot {
+                root: self.tokenization_state.new_state,
+                leaves: self.tokenization_state.leaves,
+            },
+            comment: SubStateRoot {
+                root: self.comment_state.new_state,
+                leaves: self.comment_state.leaves,
+            },
+        }
+    }
+
+    pub fn link(&self) -> StateLink {
+        StateLink {
+            old_state: self.old_state,
+            new_state: self.new_state,
+            previous_updated_at: self.previous_updated_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    pub fn new(timestamp: i64) -> Self {
         OverallState {
             oil_token_state: OilTokenState::init(vec![]),
             tokenization_state: TokenizationState::init(vec![]),
             comment_state: CommentState::init(vec![]),
             old_state: Digest::from_bytes([0; 32]),
             new_state: Digest::from_bytes([0; 32]),
-            updated_at: Local::now().to_rfc3339(),
+            previous_updated_at: timestamp,
+            updated_at: timestamp,
+            block: BlockHeader::genesis(Digest::from_bytes([0; 32]), timestamp),
         }
     }
 
+    // `timestamp` is supplied by the host, the guest has no clock of its own.
     pub fn init(
-        oil_token_leaves: Vec<String>,
-        tokenization_leaves: Vec<String>,
-        comment_leaves: Vec<String>
+        oil_token_leaves: Vec<Leaf>,
+        tokenization_leaves: Vec<Leaf>,
+        comment_leaves: Vec<Leaf>,
+        timestamp: i64
     ) -> Self {
-        let oil_token_state = OilTokenState::init(oil_token_leaves);
-        let tokenization_state = TokenizationState::init(tokenization_leaves);
-        let comment_state = CommentState::init(comment_leaves);
+        OverallState::compose(
+            OilTokenState::init(oil_token_leaves),
+            TokenizationState::init(tokenization_leaves),
+            CommentState::init(comment_leaves),
+            timestamp
+        )
+    }
 
-        let mut hasher = Keccak::v256();
-        hasher.update(&oil_token_state.overall.as_bytes());
-        hasher.update(&tokenization_state.overall.as_bytes());
-        hasher.update(&comment_state.overall.as_bytes());
-        let mut output = [0; 32];
-        hasher.finalize(&mut output);
-        let overall_digest = Digest::from_bytes(output);
+    // genesis state over three already built sub-states.
+    pub fn compose(
+        oil_token_state: OilTokenState,
+        tokenization_state: TokenizationState,
+        comment_state: CommentState,
+        timestamp: i64
+    ) -> Self {
+        let overall_digest = OverallState::compute_root(
+            &oil_token_state.new_state,
+            &tokenization_state.new_state,
+            &comment_state.new_state
+        );
 
         OverallState {
             oil_token_state,
@@ -243,23 +322,33 @@ impl OverallState {
             comment_state,
             old_state: overall_digest,
             new_state: overall_digest,
-            updated_at: Local::now().to_rfc3339(),
+            previous_updated_at: timestamp,
+            updated_at: timestamp,
+            block: BlockHeader::genesis(overall_digest, timestamp),
         }
     }
 
+    // re-anchors a full proof on top of `parent` instead of starting a new chain.
+    pub fn follow(mut self, parent: &BlockHeader) -> Result<Self, String> {
+        self.old_state = parent.root;
+        self.previous_updated_at = parent.updated_at;
+        self.block = parent.child(&self.link())?;
+        Ok(self)
+    }
+
     pub fn sync(
         &self,
         oil_token: &OilTokenState,
         tokenization: &TokenizationState,
-        comment: &CommentState
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut hasher = Keccak::v256();
-        hasher.update(&oil_token.overall.as_bytes());
-        hasher.update(&tokenization.overall.as_bytes());
-        hasher.update(&comment.overall.as_bytes());
-        let mut output = [0; 32];
-        hasher.finalize(&mut output);
-        let overall_digest = Digest::from_bytes(output);
+        comment: &CommentState,
+        timestamp: i64
+    ) -> Result<Self, String> {
+        check_timestamp(self.updated_at, timestamp)?;
+        let overall_digest = OverallState::compute_root(
+            &oil_token.new_state,
+            &tokenization.new_state,
+            &comment.new_state
+        );
 
         Ok(OverallState {
             oil_token_state: oil_token.clone(),
@@ -267,7 +356,16 @@ impl OverallState {
             comment_state: comment.clone(),
             old_state: self.new_state,
             new_state: overall_digest,
-            updated_at: Local::now().to_rfc3339(),
+            previous_updated_at: self.updated_at,
+            updated_at: timestamp,
+            block: self.block.child(
+                &(StateLink {
+                    old_state: self.new_state,
+                    new_state: overall_digest,
+                    previous_updated_at: self.updated_at,
+                    updated_at: timestamp,
+                })
+            )?,
         })
     }
 }
@@ -282,60 +380,115 @@ pub enum OilTokenAction {
     // DELETE, // delete oil token
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct OilTokenState {
-    pub old_state: Vec<Digest>,
-    pub new_state: Vec<Digest>,
-    pub overall: Digest,
-    pub leaves: usize,
-    pub action: OilTokenAction,
-    pub actor: Actor,
-}
+pub type OilTokenState = SubState<OilTokenAction>;
 
-impl OilTokenState {
-    pub fn init(db: Vec<String>) -> Self {
-        let mut oil_token_state: Vec<Digest> = vec![];
-        let mut hasher = Keccak::v256();
-        let mut hasherb = Keccak::v256();
-        for x in &db {
-            hasher.update(&x.as_bytes());
-            hasherb.update(&x.as_bytes());
-            let mut output = [0; 32];
-            hasher.clone().finalize(&mut output);
-            let digest = Digest::from_bytes(output);
-            oil_token_state.push(digest);
-        }
-        let mut output = [0; 32];
-        hasherb.finalize(&mut output);
-        let digest = Digest::from_bytes(output);
-
-        OilTokenState {
-            old_state: oil_token_state.clone(),
-            new_state: oil_token_state,
-            overall: digest,
-            leaves: db.len(),
-            action: OilTokenAction::INIT,
-            actor: Actor::SYSTEM,
+impl Action for OilTokenAction {
+    type Record = OilTokenRecord;
+
+    const TABLE: StateTable = StateTable::OilToken;
+    const INIT: Self = OilTokenAction::INIT;
+
+    // business rules of a single oil token mutation, checked before its leaf is swapped.
+    fn process(
+        &self,
+        before: Option<&OilTokenRecord>,
+        after: Option<&OilTokenRecord>
+    ) -> Result<(), String> {
+        match (self, before, after) {
+            (OilTokenAction::CREATE, None, Some(new)) => {
+                check_lot(new)?;
+                if !matches!(new.status(), "" | "pending" | "active") {
+                    return Err("New oil tokens must be pending or active".to_string());
+                }
+                Ok(())
+            }
+            (OilTokenAction::UPDATE, Some(old), Some(new)) => {
+                check_successor(old, new)?;
+                check_lot(new)?;
+                if new.owner != old.owner {
+                    return Err("Ownership only changes through BUY".to_string());
+                }
+                if old.status() == "sold" {
+                    return Err("Sold oil tokens cannot be updated".to_string());
+                }
+                Ok(())
+            }
+            (OilTokenAction::BUY, Some(old), Some(new)) => {
+                check_successor(old, new)?;
+                check_same_lot(old, new)?;
+                if old.status() != "active" {
+                    return Err("Only active oil tokens can be bought".to_string());
+                }
+                if new.status() != "sold" {
+                    return Err("Bought oil tokens must be marked sold".to_string());
+                }
+                if new.owner == old.owner {
+                    return Err("Owner cannot buy their own oil token".to_string());
+                }
+                if new.current_price()? < old.reserve_price()? {
+                    return Err("Price is below the reserve price".to_string());
+                }
+                Ok(())
+            }
+            (OilTokenAction::SELL, Some(old), Some(new)) => {
+                check_successor(old, new)?;
+                check_same_lot(old, new)?;
+                if new.owner != old.owner {
+                    return Err("Only the owner can sell an oil token".to_string());
+                }
+                if !matches!(old.status(), "pending" | "sold") {
+                    return Err("Oil token is already listed".to_string());
+                }
+                if new.status() != "active" {
+                    return Err("Listed oil tokens must be active".to_string());
+                }
+                if new.current_price()? < new.reserve_price()? {
+                    return Err("Price is below the reserve price".to_string());
+                }
+                Ok(())
+            }
+            _ => Err(format!("Invalid oil token transition {:?}", self)),
         }
     }
+}
 
-    pub fn update(&mut self, new_state: Vec<Digest>, overall: Digest) {
-        self.old_state = self.new_state.clone();
-        self.new_state = new_state;
-        self.overall = overall;
+fn check_lot(lot: &OilTokenRecord) -> Result<(), String> {
+    if lot.quantity()? <= Decimal::ZERO {
+        return Err("Quantity must be positive".to_string());
     }
+    if lot.current_price()? < Decimal::ZERO || lot.reserve_price()? < Decimal::ZERO {
+        return Err("Prices cannot be negative".to_string());
+    }
+    Ok(())
+}
 
-    pub fn process(action: OilTokenAction) {
-        match action {
-            OilTokenAction::INIT => {
-                // call the function here and make sure it workes.
-                // each function call returns the new state which will be used here to update the state.
-            }
-            _ => {}
-        }
+fn check_successor(old: &OilTokenRecord, new: &OilTokenRecord) -> Result<(), String> {
+    if new.id != old.id || new.token_id != old.token_id || new.created_at != old.created_at {
+        return Err("Oil token identity cannot change".to_string());
+    }
+    if new.updated_at < old.updated_at {
+        return Err("updated_at cannot go backwards".to_string());
     }
+    Ok(())
+}
 
-    pub fn sync() {}
+// BUY and SELL only move the lot, the goods themselves stay the same.
+fn check_same_lot(old: &OilTokenRecord, new: &OilTokenRecord) -> Result<(), String> {
+    let unchanged =
+        new.oil_type == old.oil_type &&
+        new.grade == old.grade &&
+        new.quantity == old.quantity &&
+        new.unit == old.unit &&
+        new.location == old.location &&
+        new.certification == old.certification &&
+        new.quality_report == old.quality_report &&
+        new.storage_conditions == old.storage_conditions &&
+        new.expiry_date == old.expiry_date &&
+        new.reserve_price == old.reserve_price;
+    if !unchanged {
+        return Err("Lot details cannot change on BUY or SELL".to_string());
+    }
+    Ok(())
 }
 
 // message: &[u8], signature_bytes: &[u8; 65]
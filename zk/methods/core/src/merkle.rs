@@ -0,0 +1,260 @@
+use alloc::{ format, string::String, vec::Vec };
+use serde::{ Deserialize, Serialize };
+use risc0_zkp::core::digest::Digest;
+use tiny_keccak::{ Hasher, Keccak };
+
+/// Depth of every state tree. Trees are padded with empty subtrees up to this
+/// depth so the root of a table does not depend on how many rows it has.
+pub const TREE_DEPTH: usize = 32;
+
+// domain separation between leaves and inner nodes, so a node can never be
+// passed off as a leaf (second preimage).
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub fn hash_leaf(leaf: &[u8]) -> Digest {
+    let mut hasher = Keccak::v256();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(leaf);
+    let mut output = [0; 32];
+    hasher.finalize(&mut output);
+    Digest::from_bytes(output)
+}
+
+/// Hash of the leaf held by a slot, the empty leaf marks a free slot.
+pub fn slot_hash(leaf: &[u8]) -> Digest {
+    if leaf.is_empty() { Digest::ZERO } else { hash_leaf(leaf) }
+}
+
+pub fn hash_node(left: &Digest, right: &Digest) -> Digest {
+    let mut hasher = Keccak::v256();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    let mut output = [0; 32];
+    hasher.finalize(&mut output);
+    Digest::from_bytes(output)
+}
+
+/// Roots of empty subtrees, indexed by height. `zero_hashes()[0]` is the empty leaf.
+pub fn zero_hashes() -> [Digest; TREE_DEPTH + 1] {
+    let mut zeros = [Digest::ZERO; TREE_DEPTH + 1];
+    for height in 1..=TREE_DEPTH {
+        zeros[height] = hash_node(&zeros[height - 1], &zeros[height - 1]);
+    }
+    zeros
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerkleTree {
+    // levels[0] holds the leaf hashes, levels[TREE_DEPTH] holds the root.
+    levels: Vec<Vec<Digest>>,
+}
+
+impl MerkleTree {
+    pub fn new<T: AsRef<[u8]>>(leaves: &[T]) -> Self {
+        let hashes = leaves
+            .iter()
+            .map(|leaf| slot_hash(leaf.as_ref()))
+            .collect();
+        Self::from_hashes(hashes)
+    }
+
+    pub fn from_hashes(leaf_hashes: Vec<Digest>) -> Self {
+        let zeros = zero_hashes();
+        let mut levels = Vec::with_capacity(TREE_DEPTH + 1);
+        let mut below = leaf_hashes;
+        for zero in &zeros[..TREE_DEPTH] {
+            let mut level = Vec::with_capacity(below.len().div_ceil(2));
+            for pair in below.chunks(2) {
+                let right = pair.get(1).unwrap_or(zero);
+                level.push(hash_node(&pair[0], right));
+            }
+            levels.push(below);
+            below = level;
+        }
+        levels.push(below);
+        MerkleTree { levels }
+    }
+
+    pub fn root(&self) -> Digest {
+        self.levels[TREE_DEPTH]
+            .first()
+            .copied()
+            .unwrap_or_else(|| zero_hashes()[TREE_DEPTH])
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    pub fn leaf_hashes(&self) -> &[Digest] {
+        &self.levels[0]
+    }
+
+    /// Replaces the leaf at `index`, filling the slots up to it with empty leaves,
+    /// rehashing only the path up to the root.
+    pub fn set(&mut self, index: usize, leaf_hash: Digest) -> Result<(), String> {
+        if index >> TREE_DEPTH != 0 {
+            return Err(format!("Leaf index {} is out of range", index));
+        }
+        let zeros = zero_hashes();
+        let mut position = index;
+        let mut node = leaf_hash;
+        for (height, level) in self.levels.iter_mut().enumerate() {
+            if position >= level.len() {
+                level.resize(position + 1, zeros[height]);
+            }
+            level[position] = node;
+            if height == TREE_DEPTH {
+                break;
+            }
+            let left = position & !1;
+            let right = level
+                .get(left + 1)
+                .copied()
+                .unwrap_or(zeros[height]);
+            node = hash_node(&level[left], &right);
+            position >>= 1;
+        }
+        Ok(())
+    }
+
+    /// Authentication path for the leaf at `index`, bottom up.
+    /// Any index past `len()` gives the path of an empty slot.
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >> TREE_DEPTH != 0 {
+            return None;
+        }
+        let zeros = zero_hashes();
+        let mut siblings = Vec::with_capacity(TREE_DEPTH);
+        let mut position = index;
+        for (level, zero) in self.levels.iter().zip(zeros).take(TREE_DEPTH) {
+            siblings.push(level.get(position ^ 1).copied().unwrap_or(zero));
+            position >>= 1;
+        }
+        Some(MerkleProof {
+            index: index as u64,
+            siblings,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MerkleProof {
+    pub index: u64,
+    pub siblings: Vec<Digest>,
+}
+
+impl MerkleProof {
+    /// Root obtained by hashing `leaf_hash` up along this path.
+    pub fn compute_root(&self, leaf_hash: &Digest) -> Digest {
+        let mut node = *leaf_hash;
+        let mut position = self.index;
+        for sibling in &self.siblings {
+            node = if position & 1 == 0 {
+                hash_node(&node, sibling)
+            } else {
+                hash_node(sibling, &node)
+            };
+            position >>= 1;
+        }
+        node
+    }
+
+    pub fn verify(&self, root: &Digest, leaf: &[u8]) -> bool {
+        self.siblings.len() == TREE_DEPTH &&
+            self.index >> TREE_DEPTH == 0 &&
+            self.compute_root(&hash_leaf(leaf)) == *root
+    }
+
+    /// Non-membership: the slot at `index` holds the empty leaf.
+    pub fn verify_empty(&self, root: &Digest) -> bool {
+        self.siblings.len() == TREE_DEPTH &&
+            self.index >> TREE_DEPTH == 0 &&
+            self.compute_root(&Digest::ZERO) == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn leaves(count: u8) -> Vec<Vec<u8>> {
+        (0..count).map(|i| vec![i; 3]).collect()
+    }
+
+    #[test]
+    fn proofs_verify_against_the_root() {
+        let leaves = leaves(5);
+        let tree = MerkleTree::new(&leaves);
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            assert!(proof.verify(&tree.root(), leaf));
+            assert!(!proof.verify(&tree.root(), b"other"));
+        }
+    }
+
+    #[test]
+    fn set_matches_a_rebuilt_tree() {
+        let mut leaves = leaves(5);
+        let mut tree = MerkleTree::new(&leaves);
+        leaves[3] = b"changed".to_vec();
+        tree.set(3, hash_leaf(&leaves[3])).unwrap();
+        assert_eq!(tree, MerkleTree::new(&leaves));
+        assert_eq!(tree.proof(3).unwrap().compute_root(&hash_leaf(&leaves[3])), tree.root());
+    }
+
+    #[test]
+    fn set_at_len_appends() {
+        let mut leaves = leaves(4);
+        let mut tree = MerkleTree::new(&leaves);
+        leaves.push(b"appended".to_vec());
+        let slot = tree.proof(4).unwrap();
+        assert!(slot.verify_empty(&tree.root()));
+        tree.set(4, hash_leaf(&leaves[4])).unwrap();
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.root(), MerkleTree::new(&leaves).root());
+        assert_eq!(slot.compute_root(&hash_leaf(&leaves[4])), tree.root());
+    }
+
+    #[test]
+    fn verify_empty_refuses_a_stored_leaf() {
+        let leaves = leaves(3);
+        let tree = MerkleTree::new(&leaves);
+        assert!(!tree.proof(1).unwrap().verify_empty(&tree.root()));
+        assert!(tree.proof(3).unwrap().verify_empty(&tree.root()));
+        let empty = MerkleTree::new::<Vec<u8>>(&[]);
+        assert!(empty.proof(0).unwrap().verify_empty(&zero_hashes()[TREE_DEPTH]));
+    }
+
+    #[test]
+    fn set_past_len_leaves_empty_slots() {
+        let mut tree = MerkleTree::new(&leaves(2));
+        let slot = tree.proof(5).unwrap();
+        assert!(slot.verify_empty(&tree.root()));
+        tree.set(5, hash_leaf(b"far")).unwrap();
+        let mut slotted = leaves(2);
+        slotted.extend([vec![], vec![], vec![], b"far".to_vec()]);
+        assert_eq!(tree, MerkleTree::new(&slotted));
+        assert_eq!(slot.compute_root(&hash_leaf(b"far")), tree.root());
+        // clearing the slot again gives back the root of the shorter table
+        tree.set(5, Digest::ZERO).unwrap();
+        assert_eq!(tree.root(), MerkleTree::new(&leaves(2)).root());
+    }
+
+    #[test]
+    fn out_of_range_index_is_refused() {
+        let mut tree = MerkleTree::new(&leaves(3));
+        assert!(tree.proof(1 << TREE_DEPTH).is_none());
+        assert!(tree.set(1 << TREE_DEPTH, Digest::ZERO).is_err());
+        let mut proof = tree.proof(0).unwrap();
+        proof.index = 1 << TREE_DEPTH;
+        assert!(!proof.verify(&tree.root(), &leaves(1)[0]));
+    }
+}
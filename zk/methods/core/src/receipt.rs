@@ -0,0 +1,34 @@
+use alloc::string::{ String, ToString };
+use risc0_zkp::core::digest::Digest;
+use risc0_zkvm::{ InnerReceipt, Receipt };
+
+/// Verifies `receipt` against `image_id`. A dev-mode fake receipt proves nothing and
+/// passes whenever RISC0_DEV_MODE is set, so it is refused unless `accept_fake` is set.
+pub fn verify_receipt(receipt: &Receipt, image_id: Digest, accept_fake: bool) -> Result<(), String> {
+    let proven = matches!(
+        receipt.inner,
+        InnerReceipt::Composite(_) | InnerReceipt::Succinct(_) | InnerReceipt::Groth16(_)
+    );
+    if !proven && !accept_fake {
+        return Err("Dev-mode receipts are refused".to_string());
+    }
+    receipt.verify(image_id).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use risc0_zkvm::{ FakeReceipt, ReceiptClaim };
+
+    #[test]
+    fn fake_receipt_is_refused_unless_accepted() {
+        let image_id = Digest::from([7u32; 8]);
+        let journal = alloc::vec![1, 2, 3];
+        let claim = ReceiptClaim::ok(image_id, journal.clone());
+        let receipt = Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal);
+        assert_eq!(
+            verify_receipt(&receipt, image_id, false),
+            Err("Dev-mode receipts are refused".to_string())
+        );
+    }
+}
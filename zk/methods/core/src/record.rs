@@ -0,0 +1,154 @@
+use serde::{ Deserialize, Serialize };
+use rust_decimal::Decimal;
+use core::str::FromStr;
+use alloc::{ format, string::{ String, ToString } };
+
+use crate::leaf::{ Leaf, LeafEncoder };
+use crate::StateTable;
+
+// Typed copies of the committed rows, shared by the host (built from the db entities)
+// and the guests (business rules + leaf hashing). Decimals travel as their normalized string.
+
+pub trait Record {
+    // primary key of the row
+    fn id(&self) -> i32;
+
+    // canonical encoding of the row, see leaf.rs
+    fn leaf(&self) -> Leaf;
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct OilTokenRecord {
+    pub id: i32,
+    pub token_id: i32,
+    pub oil_type: String,
+    pub grade: String,
+    pub quantity: String,
+    pub unit: String,
+    pub location: String,
+    pub certification: String,
+    pub quality_report: String,
+    pub storage_conditions: String,
+    pub expiry_date: i64,
+    pub current_price: String,
+    pub reserve_price: String,
+    pub status: Option<String>,
+    pub owner: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Record for OilTokenRecord {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn leaf(&self) -> Leaf {
+        LeafEncoder::new(StateTable::OilToken)
+            .i32(self.id)
+            .i32(self.token_id)
+            .str(&self.oil_type)
+            .str(&self.grade)
+            .str(&self.quantity)
+            .str(&self.unit)
+            .str(&self.location)
+            .str(&self.certification)
+            .str(&self.quality_report)
+            .str(&self.storage_conditions)
+            .i64(self.expiry_date)
+            .str(&self.current_price)
+            .str(&self.reserve_price)
+            .opt_str(self.status.as_deref())
+            .str(&self.owner)
+            .i64(self.created_at)
+            .i64(self.updated_at)
+            .finish()
+    }
+}
+
+impl OilTokenRecord {
+    pub fn quantity(&self) -> Result<Decimal, String> {
+        parse_decimal(&self.quantity)
+    }
+
+    pub fn current_price(&self) -> Result<Decimal, String> {
+        parse_decimal(&self.current_price)
+    }
+
+    pub fn reserve_price(&self) -> Result<Decimal, String> {
+        parse_decimal(&self.reserve_price)
+    }
+
+    pub fn status(&self) -> &str {
+        self.status.as_deref().unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TokenizationRecord {
+    pub id: i32,
+    pub oil_token_id: i32,
+    pub tokenizer_id: String,
+    pub amount: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Record for TokenizationRecord {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn leaf(&self) -> Leaf {
+        LeafEncoder::new(StateTable::Tokenization)
+            .i32(self.id)
+            .i32(self.oil_token_id)
+            .str(&self.tokenizer_id)
+            .str(&self.amount)
+            .i64(self.created_at)
+            .i64(self.updated_at)
+            .finish()
+    }
+}
+
+impl TokenizationRecord {
+    pub fn amount(&self) -> Result<Decimal, String> {
+        parse_decimal(&self.amount)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CommentRecord {
+    pub id: i32,
+    pub oil_token_id: i32,
+    pub user: String,
+    pub content: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Record for CommentRecord {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn leaf(&self) -> Leaf {
+        LeafEncoder::new(StateTable::Comment)
+            .i32(self.id)
+            .i32(self.oil_token_id)
+            .str(&self.user)
+            .str(&self.content)
+            .i64(self.created_at)
+            .i64(self.updated_at)
+            .finish()
+    }
+}
+
+// only the normalized form is accepted, so one amount has exactly one leaf.
+fn parse_decimal(value: &str) -> Result<Decimal, String> {
+    let decimal = Decimal::from_str(value).map_err(|_| format!("Invalid decimal {}", value))?;
+    if decimal.normalize().to_string() != value {
+        return Err(format!("Decimal {} is not normalized", value));
+    }
+    Ok(decimal)
+}
@@ -0,0 +1,110 @@
+use alloc::{ format, string::{ String, ToString } };
+use k256::ecdsa::{ RecoveryId, Signature, VerifyingKey };
+use serde::{ Deserialize, Serialize };
+use tiny_keccak::{ Hasher, Keccak };
+
+use crate::eip712::{ recover_typed_signer, Eip712Domain, Login };
+use crate::{ SiweMessage, VerifyCommit, VerifyParams };
+
+pub(crate) fn keccak256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    for part in parts {
+        hasher.update(part);
+    }
+    let mut output = [0; 32];
+    hasher.finalize(&mut output);
+    output
+}
+
+/// EIP-191 digest of a personal_sign message: keccak("\x19Ethereum Signed Message:\n" || len || message).
+pub fn eip191_hash(message: &str) -> [u8; 32] {
+    let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
+    keccak256(&[prefix.as_bytes(), message.as_bytes()])
+}
+
+/// Recovers the address that signed `message`, from a 65-byte `r || s || v` hex signature.
+pub fn recover_ethereum_address(signature_hex: &str, message: &str) -> Result<[u8; 20], String> {
+    recover_prehash(&eip191_hash(message), signature_hex)
+}
+
+/// Recovers the address that signed the 32-byte `hash`, from a 65-byte `r || s || v` hex signature.
+pub fn recover_prehash(hash: &[u8; 32], signature_hex: &str) -> Result<[u8; 20], String> {
+    let signature_bytes = hex
+        ::decode(signature_hex.strip_prefix("0x").unwrap_or(signature_hex))
+        .map_err(|_| "Invalid signature hex".to_string())?;
+    if signature_bytes.len() != 65 {
+        return Err(format!("Expected 65-byte signature, got {}", signature_bytes.len()));
+    }
+
+    let signature = Signature::try_from(&signature_bytes[..64]).map_err(|_|
+        "Invalid signature".to_string()
+    )?;
+    let v = signature_bytes[64];
+    let recovery_id = RecoveryId::try_from(if v >= 27 { v - 27 } else { v % 2 }).map_err(|_|
+        "Invalid recovery id".to_string()
+    )?;
+
+    let key = VerifyingKey::recover_from_prehash(hash, &signature, recovery_id).map_err(|_|
+        "Failed to recover public key".to_string()
+    )?;
+
+    // address is the last 20 bytes of keccak(x || y)
+    let point = key.to_encoded_point(false);
+    let hash = keccak256(&[&point.as_bytes()[1..]]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    Ok(address)
+}
+
+/// Lowercase `0x` hex form of an address.
+pub fn address_hex(address: &[u8; 20]) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+// How the login message was signed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureScheme {
+    // personal_sign over the SIWE text
+    #[default]
+    Eip191,
+    // eth_signTypedData_v4 over the `Login` struct of the same SIWE message
+    Eip712,
+}
+
+impl VerifyParams {
+    /// Checks that `message` is a SIWE message for `expected_addr`, scoped to `domain` and
+    /// `chain_id`, valid at `timestamp` and signed by that address with `scheme`. A malformed message or
+    /// signature is reported as not verified rather than as an error.
+    pub fn verify(self) -> VerifyCommit {
+        let expected = self.expected_addr.to_ascii_lowercase();
+        let message = SiweMessage::parse(&self.message).ok();
+        let checked = message.as_ref().is_some_and(|message| {
+            message.address.to_ascii_lowercase() == expected &&
+                message.check(&self.domain, self.chain_id, self.timestamp).is_ok()
+        });
+        let signer = match (self.scheme, &message) {
+            (SignatureScheme::Eip191, _) =>
+                recover_ethereum_address(&self.signature_bytes, &self.message),
+            (SignatureScheme::Eip712, Some(message)) =>
+                recover_typed_signer(
+                    &Eip712Domain::new(self.chain_id),
+                    &Login::from(message),
+                    &self.signature_bytes
+                ),
+            (SignatureScheme::Eip712, None) => Err("Not a SIWE message".to_string()),
+        };
+        let verified =
+            checked &&
+            signer.map(|address| address_hex(&address) == expected).unwrap_or(false);
+
+        VerifyCommit {
+            verified,
+            address: expected,
+            timestamp: self.timestamp,
+            username: self.username,
+            scheme: self.scheme,
+            message,
+        }
+    }
+}
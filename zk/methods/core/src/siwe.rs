@@ -0,0 +1,369 @@
+use alloc::{ format, string::{ String, ToString }, vec::Vec };
+use serde::{ Deserialize, Serialize };
+
+// Sign-In with Ethereum (EIP-4361) message, the text a wallet signs to log in:
+//
+//   {domain} wants you to sign in with your Ethereum account:
+//   {address}
+//
+//   {statement}
+//
+//   URI: {uri}
+//   Version: 1
+//   Chain ID: {chain_id}
+//   Nonce: {nonce}
+//   Issued At: {issued_at}
+//   Expiration Time: {expiration_time}
+//
+// The statement and every field after Issued At are optional. Times are RFC 3339, kept as unix seconds.
+
+const HEADER: &str = " wants you to sign in with your Ethereum account:";
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SiweMessage {
+    pub domain: String,
+    pub address: String,
+    pub statement: Option<String>,
+    pub uri: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: i64,
+    pub expiration_time: Option<i64>,
+    pub not_before: Option<i64>,
+}
+
+impl SiweMessage {
+    pub fn parse(message: &str) -> Result<Self, String> {
+        let mut lines = message.lines();
+        let domain = lines
+            .next()
+            .and_then(|line| line.strip_suffix(HEADER))
+            .filter(|domain| !domain.is_empty())
+            .ok_or_else(|| "Missing SIWE header".to_string())?;
+        let address = lines
+            .next()
+            .filter(|address| address.len() == 42 && address.starts_with("0x"))
+            .ok_or_else(|| "Missing SIWE address".to_string())?;
+        if lines.next() != Some("") {
+            return Err("Expected an empty line after the address".to_string());
+        }
+
+        // optional statement, always followed by an empty line
+        let mut statement = None;
+        let mut next = lines.next();
+        if let Some(line) = next.filter(|line| !line.is_empty()) {
+            statement = Some(line.to_string());
+            if lines.next() != Some("") {
+                return Err("Expected an empty line after the statement".to_string());
+            }
+        }
+        if statement.is_some() || next == Some("") {
+            next = lines.next();
+        }
+
+        let fields: Vec<&str> = next.into_iter().chain(lines).collect();
+        let mut fields = fields.iter().copied().peekable();
+        let mut field = |name: &str| -> Option<&str> {
+            let value = fields.peek()?.strip_prefix(name)?.strip_prefix(": ")?;
+            fields.next();
+            Some(value)
+        };
+        let uri = field("URI").ok_or_else(|| "Missing SIWE URI".to_string())?.to_string();
+        let version = field("Version").ok_or_else(|| "Missing SIWE version".to_string())?;
+        if version != "1" {
+            return Err(format!("Unsupported SIWE version {}", version));
+        }
+        let chain_id = field("Chain ID")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| "Missing or invalid SIWE chain id".to_string())?;
+        let nonce = field("Nonce").ok_or_else(|| "Missing SIWE nonce".to_string())?;
+        if nonce.len() < 8 || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("SIWE nonce must be at least 8 alphanumeric characters".to_string());
+        }
+        let issued_at = parse_rfc3339(
+            field("Issued At").ok_or_else(|| "Missing SIWE issued at".to_string())?
+        )?;
+        let expiration_time = field("Expiration Time").map(parse_rfc3339).transpose()?;
+        let not_before = field("Not Before").map(parse_rfc3339).transpose()?;
+        // request id and resources are allowed but not committed
+        field("Request ID");
+        if fields.peek() == Some(&"Resources:") {
+            fields.next();
+            while fields.peek().is_some_and(|line| line.starts_with("- ")) {
+                fields.next();
+            }
+        }
+        if let Some(line) = fields.next() {
+            return Err(format!("Unexpected SIWE line {:?}", line));
+        }
+
+        Ok(SiweMessage {
+            domain: domain.to_string(),
+            address: address.to_string(),
+            statement,
+            uri,
+            version: version.to_string(),
+            chain_id,
+            nonce: nonce.to_string(),
+            issued_at,
+            expiration_time,
+            not_before,
+        })
+    }
+
+    /// Text of the message, as signed by the wallet.
+    pub fn to_message(&self) -> String {
+        let mut message = format!("{}{}\n{}\n\n", self.domain, HEADER, self.address);
+        if let Some(statement) = &self.statement {
+            message.push_str(&format!("{}\n\n", statement));
+        } else {
+            message.push('\n');
+        }
+        message.push_str(
+            &format!(
+                "URI: {}\nVersion: {}\nChain ID: {}\nNonce: {}\nIssued At: {}",
+                self.uri,
+                self.version,
+                self.chain_id,
+                self.nonce,
+                format_rfc3339(self.issued_at)
+            )
+        );
+        if let Some(expiration_time) = self.expiration_time {
+            message.push_str(&format!("\nExpiration Time: {}", format_rfc3339(expiration_time)));
+        }
+        if let Some(not_before) = self.not_before {
+            message.push_str(&format!("\nNot Before: {}", format_rfc3339(not_before)));
+        }
+        message
+    }
+
+    /// Checks the message is for `domain` and `chain_id` and valid at `now`.
+    pub fn check(&self, domain: &str, chain_id: u64, now: i64) -> Result<(), String> {
+        if self.domain != domain {
+            return Err(format!("Message is for domain {}, expected {}", self.domain, domain));
+        }
+        if self.chain_id != chain_id {
+            return Err(format!("Message is for chain {}, expected {}", self.chain_id, chain_id));
+        }
+        if self.issued_at > now {
+            return Err("Message is issued in the future".to_string());
+        }
+        if self.expiration_time.is_some_and(|expiration_time| expiration_time <= now) {
+            return Err("Message has expired".to_string());
+        }
+        if self.not_before.is_some_and(|not_before| not_before > now) {
+            return Err("Message is not valid yet".to_string());
+        }
+        Ok(())
+    }
+}
+
+// days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = (if days >= 0 { days } else { days - 146096 }) / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (if month <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, month, day)
+}
+
+fn digits(s: &str, range: core::ops::Range<usize>) -> Result<i64, String> {
+    s.get(range)
+        .filter(|d| d.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|d| d.parse().ok())
+        .ok_or_else(|| format!("Invalid RFC 3339 time {:?}", s))
+}
+
+/// Unix seconds of an RFC 3339 time such as `2025-01-01T12:00:00.000Z` or `2025-01-01T14:00:00+02:00`.
+pub fn parse_rfc3339(s: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid RFC 3339 time {:?}", s);
+    let bytes = s.as_bytes();
+    if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[13] != b':' || bytes[16] != b':' {
+        return Err(invalid());
+    }
+    if !matches!(bytes[10], b'T' | b't') {
+        return Err(invalid());
+    }
+    let (year, month, day) = (digits(s, 0..4)?, digits(s, 5..7)?, digits(s, 8..10)?);
+    let (hour, minute, second) = (digits(s, 11..13)?, digits(s, 14..16)?, digits(s, 17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return Err(invalid());
+    }
+
+    // fractional seconds are dropped
+    let mut rest = &s[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let end = fraction.find(|c: char| !c.is_ascii_digit()).unwrap_or(fraction.len());
+        if end == 0 {
+            return Err(invalid());
+        }
+        rest = &fraction[end..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => {
+                    return Err(invalid());
+                }
+            };
+            sign * (digits(rest, 1..3)? * 3600 + digits(rest, 4..6)? * 60)
+        }
+        _ => {
+            return Err(invalid());
+        }
+    };
+
+    Ok(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+/// RFC 3339 UTC time of unix seconds, e.g. `2025-01-01T12:00:00Z`.
+pub fn format_rfc3339(timestamp: i64) -> String {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(86400));
+    let seconds = timestamp.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ siwe_message, NOW };
+
+    fn message() -> SiweMessage {
+        SiweMessage {
+            not_before: Some(NOW - 60),
+            ..siwe_message()
+        }
+    }
+
+    #[test]
+    fn to_message_round_trips() {
+        let full = message();
+        let bare = SiweMessage {
+            statement: None,
+            expiration_time: None,
+            not_before: None,
+            ..message()
+        };
+        for message in [full, bare] {
+            assert_eq!(SiweMessage::parse(&message.to_message()), Ok(message.clone()));
+        }
+    }
+
+    #[test]
+    fn to_message_is_the_signed_text() {
+        assert_eq!(
+            message().to_message(),
+            "localhost:3001 wants you to sign in with your Ethereum account:\n\
+             0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266\n\
+             \n\
+             Sign in to the Oil Tokenization Platform\n\
+             \n\
+             URI: http://localhost:3001\n\
+             Version: 1\n\
+             Chain ID: 1\n\
+             Nonce: abcdefgh12345678\n\
+             Issued At: 2025-01-01T12:00:00Z\n\
+             Expiration Time: 2025-01-01T12:05:00Z\n\
+             Not Before: 2025-01-01T11:59:00Z"
+        );
+    }
+
+    #[test]
+    fn request_id_and_resources_are_accepted() {
+        let text = format!(
+            "{}\nRequest ID: 42\nResources:\n- https://example.com/a\n- https://example.com/b",
+            message().to_message()
+        );
+        assert_eq!(SiweMessage::parse(&text), Ok(message()));
+    }
+
+    #[test]
+    fn malformed_messages_are_refused() {
+        let text = message().to_message();
+        let malformed = [
+            text.replace(" wants you to sign in", " wants to sign in"),
+            text.replace("0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266", "0xf39Fd6"),
+            text.replace("\n\nSign in", "\nSign in"),
+            text.replace("URI: ", "Uri: "),
+            text.replace("Version: 1", "Version: 2"),
+            text.replace("Chain ID: 1", "Chain ID: one"),
+            text.replace("Nonce: abcdefgh12345678", "Nonce: short"),
+            text.replace("Nonce: abcdefgh12345678", "Nonce: abcd-efgh-1234"),
+            text.replace("2025-01-01T12:00:00Z", "2025-01-01 12:00:00"),
+            text.replace("2025-01-01T12:05:00Z", "2025-13-01T12:05:00Z"),
+            format!("{}\nUnknown: field", text),
+            String::new(),
+        ];
+        for text in malformed {
+            assert!(SiweMessage::parse(&text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn check_accepts_a_valid_message() {
+        assert_eq!(message().check("localhost:3001", 1, NOW), Ok(()));
+    }
+
+    #[test]
+    fn check_refuses_another_domain_or_chain() {
+        assert!(message().check("evil.example", 1, NOW).is_err());
+        assert!(message().check("localhost:3001", 5, NOW).is_err());
+    }
+
+    #[test]
+    fn check_refuses_expired_messages() {
+        let message = message();
+        assert!(message.check("localhost:3001", 1, NOW + 299).is_ok());
+        assert!(message.check("localhost:3001", 1, NOW + 300).is_err());
+    }
+
+    #[test]
+    fn check_refuses_messages_before_not_before() {
+        let message = SiweMessage {
+            not_before: Some(NOW + 60),
+            ..message()
+        };
+        assert!(message.check("localhost:3001", 1, NOW + 59).is_err());
+        assert!(message.check("localhost:3001", 1, NOW + 60).is_ok());
+    }
+
+    #[test]
+    fn check_refuses_messages_issued_in_the_future() {
+        assert!(message().check("localhost:3001", 1, NOW - 1).is_err());
+    }
+
+    #[test]
+    fn rfc3339_times_round_trip() {
+        assert_eq!(parse_rfc3339("2025-01-01T12:00:00Z"), Ok(NOW));
+        assert_eq!(parse_rfc3339("2025-01-01T12:00:00.123Z"), Ok(NOW));
+        assert_eq!(parse_rfc3339("2025-01-01T14:00:00+02:00"), Ok(NOW));
+        assert_eq!(format_rfc3339(NOW), "2025-01-01T12:00:00Z");
+        assert_eq!(parse_rfc3339(&format_rfc3339(951782400)), Ok(951782400));
+    }
+}
@@ -0,0 +1,311 @@
+use alloc::{ collections::BTreeMap, format, string::String, vec, vec::Vec };
+use serde::{ Deserialize, Serialize };
+use risc0_zkp::core::digest::Digest;
+
+use crate::merkle::{ hash_leaf, hash_node, zero_hashes, MerkleProof, TREE_DEPTH };
+use crate::{ leaf_id, Leaf, OverallState, StateTable };
+
+// Sparse Merkle tree over the whole u32 key space, keyed by the primary key of a row.
+// Absent keys are empty leaves, so a path to an empty leaf proves the record is not there.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SparseMerkleTree {
+    // nodes[height] holds the non-empty nodes of that height by index.
+    nodes: Vec<BTreeMap<u64, Digest>>,
+}
+
+// primary keys are i32, every i32 maps to its own u32 slot.
+fn key_index(id: i32) -> u64 {
+    (id as u32) as u64
+}
+
+impl SparseMerkleTree {
+    /// Tree of the rows of `table`. Every leaf must be stored under the primary key it
+    /// encodes and every key only once, or a record could be moved away from its slot
+    /// and then proven absent.
+    pub fn new<T: AsRef<[u8]>>(table: StateTable, leaves: &[(i32, T)]) -> Result<Self, String> {
+        let mut level = BTreeMap::new();
+        for (id, leaf) in leaves {
+            if leaf_id(table, leaf.as_ref()) != Some(*id) {
+                return Err(format!("{:?} leaf is not stored under its key {}", table, id));
+            }
+            if level.insert(key_index(*id), hash_leaf(leaf.as_ref())).is_some() {
+                return Err(format!("{:?} key {} is stored twice", table, id));
+            }
+        }
+        let zeros = zero_hashes();
+        let mut nodes = Vec::with_capacity(TREE_DEPTH + 1);
+        for zero in &zeros[..TREE_DEPTH] {
+            let mut parents = BTreeMap::new();
+            for (index, node) in &level {
+                let parent = index >> 1;
+                if parents.contains_key(&parent) {
+                    continue;
+                }
+                let sibling = level
+                    .get(&(index ^ 1))
+                    .copied()
+                    .unwrap_or(*zero);
+                let hash = if index & 1 == 0 {
+                    hash_node(node, &sibling)
+                } else {
+                    hash_node(&sibling, node)
+                };
+                parents.insert(parent, hash);
+            }
+            nodes.push(level);
+            level = parents;
+        }
+        nodes.push(level);
+        Ok(SparseMerkleTree { nodes })
+    }
+
+    // node at `height` and `index`, or the root of an empty subtree from `zeros`.
+    fn node(&self, zeros: &[Digest; TREE_DEPTH + 1], height: usize, index: u64) -> Digest {
+        self.nodes
+            .get(height)
+            .and_then(|level| level.get(&index))
+            .copied()
+            .unwrap_or(zeros[height])
+    }
+
+    pub fn root(&self) -> Digest {
+        self.node(&zero_hashes(), TREE_DEPTH, 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.first().map_or(0, |leaves| leaves.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, id: i32) -> Option<Digest> {
+        self.nodes.first().and_then(|leaves| leaves.get(&key_index(id)).copied())
+    }
+
+    pub fn insert(&mut self, id: i32, leaf: &[u8]) {
+        self.set(id, hash_leaf(leaf));
+    }
+
+    pub fn remove(&mut self, id: i32) {
+        self.set(id, Digest::ZERO);
+    }
+
+    // rehashes the path of `id` in O(TREE_DEPTH), dropping nodes that became empty.
+    fn set(&mut self, id: i32, leaf_hash: Digest) {
+        let zeros = zero_hashes();
+        if self.nodes.is_empty() {
+            self.nodes = vec![BTreeMap::new(); TREE_DEPTH + 1];
+        }
+        let mut index = key_index(id);
+        let mut node = leaf_hash;
+        for (height, zero) in zeros.iter().enumerate() {
+            if node == *zero {
+                self.nodes[height].remove(&index);
+            } else {
+                self.nodes[height].insert(index, node);
+            }
+            if height == TREE_DEPTH {
+                break;
+            }
+            let sibling = self.node(&zeros, height, index ^ 1);
+            node = if index & 1 == 0 {
+                hash_node(&node, &sibling)
+            } else {
+                hash_node(&sibling, &node)
+            };
+            index >>= 1;
+        }
+    }
+
+    /// Path of the slot of `id`, whether or not a record is stored there.
+    pub fn proof(&self, id: i32) -> MerkleProof {
+        let zeros = zero_hashes();
+        let mut index = key_index(id);
+        let mut siblings = Vec::with_capacity(TREE_DEPTH);
+        for height in 0..TREE_DEPTH {
+            siblings.push(self.node(&zeros, height, index ^ 1));
+            index >>= 1;
+        }
+        MerkleProof {
+            index: key_index(id),
+            siblings,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KeyedParams {
+    pub oil_token_leaves: Vec<(i32, Leaf)>,
+    pub tokenization_leaves: Vec<(i32, Leaf)>,
+    pub comment_leaves: Vec<(i32, Leaf)>,
+}
+
+// Roots of the three tables keyed by primary key, the live set of records.
+//
+// It is committed on its own rather than in the block chain. Blocks commit every row in
+// table order, which is what transitions and deltas update in O(TREE_DEPTH) and what
+// inclusion proofs are checked against. The keyed state commits a different set, the
+// live records only (cancelled lots are left out), so its roots cannot be derived from a
+// block. It answers "is record `id` live right now", and membership and non-membership
+// proofs are checked against the receipt of the init_keyed run that committed it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct KeyedState {
+    pub oil_token_state: Digest,
+    pub tokenization_state: Digest,
+    pub comment_state: Digest,
+    pub overall: Digest,
+}
+
+impl KeyedState {
+    pub fn init(params: &KeyedParams) -> Result<Self, String> {
+        let oil_token_state = SparseMerkleTree::new(
+            StateTable::OilToken,
+            &params.oil_token_leaves
+        )?.root();
+        let tokenization_state = SparseMerkleTree::new(
+            StateTable::Tokenization,
+            &params.tokenization_leaves
+        )?.root();
+        let comment_state = SparseMerkleTree::new(
+            StateTable::Comment,
+            &params.comment_leaves
+        )?.root();
+        Ok(KeyedState {
+            oil_token_state,
+            tokenization_state,
+            comment_state,
+            overall: OverallState::compute_root(
+                &oil_token_state,
+                &tokenization_state,
+                &comment_state
+            ),
+        })
+    }
+
+    pub fn sub_state_root(&self, table: StateTable) -> Digest {
+        match table {
+            StateTable::OilToken => self.oil_token_state,
+            StateTable::Tokenization => self.tokenization_state,
+            StateTable::Comment => self.comment_state,
+        }
+    }
+}
+
+// Membership (leaf is Some) or non-membership (leaf is None) of the record `id`
+// in a published KeyedState.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct KeyedProof {
+    pub table: StateTable,
+    pub id: i32,
+    pub leaf: Option<Leaf>,
+    pub proof: MerkleProof,
+    pub sub_state_root: Digest,
+    pub overall_root: Digest,
+}
+
+impl KeyedProof {
+    pub fn verify(&self, state: &KeyedState) -> bool {
+        let recomputed = OverallState::compute_root(
+            &state.oil_token_state,
+            &state.tokenization_state,
+            &state.comment_state
+        );
+        // a member leaf must encode the id it is proven under
+        let leaf_matches = match &self.leaf {
+            Some(leaf) =>
+                leaf_id(self.table, leaf) == Some(self.id) &&
+                    self.proof.verify(&self.sub_state_root, leaf),
+            None => self.proof.verify_empty(&self.sub_state_root),
+        };
+        recomputed == state.overall &&
+            self.overall_root == state.overall &&
+            self.sub_state_root == state.sub_state_root(self.table) &&
+            self.proof.index == key_index(self.id) &&
+            leaf_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LeafEncoder;
+
+    fn leaf(table: StateTable, id: i32, content: &str) -> Leaf {
+        LeafEncoder::new(table).i32(id).str(content).finish()
+    }
+
+    fn comments(ids: &[i32]) -> Vec<(i32, Leaf)> {
+        ids.iter()
+            .map(|id| (*id, leaf(StateTable::Comment, *id, "comment")))
+            .collect()
+    }
+
+    fn keyed(comment_leaves: Vec<(i32, Leaf)>) -> KeyedState {
+        KeyedState::init(
+            &(KeyedParams {
+                oil_token_leaves: vec![],
+                tokenization_leaves: vec![],
+                comment_leaves,
+            })
+        ).unwrap()
+    }
+
+    #[test]
+    fn members_and_absent_keys_verify() {
+        let rows = comments(&[1, 2, 7]);
+        let tree = SparseMerkleTree::new(StateTable::Comment, &rows).unwrap();
+        assert!(tree.proof(7).verify(&tree.root(), &rows[2].1));
+        assert!(tree.proof(3).verify_empty(&tree.root()));
+        assert!(!tree.proof(2).verify_empty(&tree.root()));
+    }
+
+    #[test]
+    fn insert_and_remove_match_a_rebuilt_tree() {
+        let mut tree = SparseMerkleTree::new(StateTable::Comment, &comments(&[1, 2])).unwrap();
+        tree.insert(9, &leaf(StateTable::Comment, 9, "comment"));
+        tree.remove(1);
+        let rebuilt = SparseMerkleTree::new(StateTable::Comment, &comments(&[2, 9])).unwrap();
+        assert_eq!(tree.root(), rebuilt.root());
+        assert_eq!(tree.len(), 2);
+    }
+
+    #[test]
+    fn leaves_under_another_key_are_refused() {
+        let mut rows = comments(&[1, 2]);
+        rows[1].0 = 5;
+        assert!(SparseMerkleTree::new(StateTable::Comment, &rows).is_err());
+        // a leaf of another table is refused too
+        let oil_token = vec![(1, leaf(StateTable::OilToken, 1, "lot"))];
+        assert!(SparseMerkleTree::new(StateTable::Comment, &oil_token).is_err());
+    }
+
+    #[test]
+    fn duplicate_keys_are_refused() {
+        assert!(SparseMerkleTree::new(StateTable::Comment, &comments(&[1, 2, 1])).is_err());
+    }
+
+    #[test]
+    fn keyed_proofs_check_membership_and_key() {
+        let rows = comments(&[1, 2]);
+        let tree = SparseMerkleTree::new(StateTable::Comment, &rows).unwrap();
+        let state = keyed(rows.clone());
+        let proof = |id: i32, leaf: Option<Leaf>| KeyedProof {
+            table: StateTable::Comment,
+            id,
+            leaf,
+            proof: tree.proof(id),
+            sub_state_root: state.comment_state,
+            overall_root: state.overall,
+        };
+        assert!(proof(2, Some(rows[1].1.clone())).verify(&state));
+        assert!(proof(3, None).verify(&state));
+        // a stored record cannot be proven absent
+        assert!(!proof(2, None).verify(&state));
+        // nor proven under another id
+        let mut moved = proof(2, Some(rows[1].1.clone()));
+        moved.id = 3;
+        assert!(!moved.verify(&state));
+    }
+}
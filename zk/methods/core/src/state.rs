@@ -0,0 +1,66 @@
+use alloc::{ string::String, vec::Vec };
+use core::fmt::Debug;
+use serde::{ de::DeserializeOwned, Deserialize, Serialize };
+use risc0_zkp::core::digest::Digest;
+
+use crate::record::Record;
+use crate::{ Actor, Leaf, MerkleTree, StateTable };
+
+// The action enum of one committed table. It ties the table to its record type
+// and carries the business rules every mutation of that table is checked against.
+pub trait Action: Clone + Debug + Eq + Serialize + DeserializeOwned {
+    type Record: Record + Clone + Debug + Eq + Serialize + DeserializeOwned;
+
+    const TABLE: StateTable;
+    const INIT: Self;
+
+    // business rules of a single mutation, checked before its leaf is swapped.
+    fn process(
+        &self,
+        before: Option<&Self::Record>,
+        after: Option<&Self::Record>
+    ) -> Result<(), String>;
+}
+
+// Merkle state of one committed table, the same for every table but its action enum.
+// Only roots and the count of stored rows are committed, the leaves themselves stay a private input
+// of the guest so the journal has a fixed size and reveals nothing per record.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(bound = "")]
+pub struct SubState<A: Action> {
+    pub old_state: Digest,
+    pub new_state: Digest,
+    pub leaves: u64,
+    pub action: A,
+    pub actor: Actor,
+}
+
+impl<A: Action> SubState<A> {
+    pub fn init(db: Vec<Leaf>) -> Self {
+        let root = MerkleTree::new(&db).root();
+
+        SubState {
+            old_state: root,
+            new_state: root,
+            leaves: db
+                .iter()
+                .filter(|leaf| !leaf.is_empty())
+                .count() as u64,
+            action: A::INIT,
+            actor: Actor::SYSTEM,
+        }
+    }
+
+    pub fn update(&mut self, root: Digest, leaves: u64) {
+        self.old_state = self.new_state;
+        self.new_state = root;
+        self.leaves = leaves;
+    }
+}
+
+// Input of the init_state guest, which commits the table followed by its SubState.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SubStateParams {
+    pub table: StateTable,
+    pub leaves: Vec<Leaf>,
+}
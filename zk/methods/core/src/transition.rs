@@ -0,0 +1,294 @@
+use alloc::{ boxed::Box, format, string::{ String, ToString }, vec::Vec };
+use serde::{ Deserialize, Serialize };
+use risc0_zkp::core::digest::Digest;
+
+use crate::block::BlockHeader;
+use crate::chain::StateLink;
+use crate::merkle::{ hash_leaf, TREE_DEPTH };
+use crate::record::{ CommentRecord, OilTokenRecord, Record, TokenizationRecord };
+use crate::{
+    Action,
+    Actor,
+    CommentAction,
+    slot,
+    Leaf,
+    MerkleProof,
+    OilTokenAction,
+    OverallState,
+    StateTable,
+    TokenizationAction,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SubStateRoot {
+    pub root: Digest,
+    pub leaves: u64,
+}
+
+impl SubStateRoot {
+    /// Swaps the leaf hash at the slot of `proof` in O(TREE_DEPTH). A created row fills its
+    /// empty slot and a deleted row (`new_leaf` is the empty leaf) empties it again.
+    pub fn apply(
+        &mut self,
+        proof: &MerkleProof,
+        old_leaf: &Digest,
+        new_leaf: &Digest
+    ) -> Result<(), String> {
+        if proof.siblings.len() != TREE_DEPTH || proof.index >> TREE_DEPTH != 0 {
+            return Err("Merkle path has the wrong depth".to_string());
+        }
+        if *old_leaf == Digest::ZERO && *new_leaf == Digest::ZERO {
+            return Err("Empty slot is left unchanged".to_string());
+        }
+        if proof.compute_root(old_leaf) != self.root {
+            return Err("Merkle path does not match the current root".to_string());
+        }
+        if *old_leaf == Digest::ZERO {
+            self.leaves += 1;
+        } else if *new_leaf == Digest::ZERO {
+            self.leaves -= 1;
+        }
+        self.root = proof.compute_root(new_leaf);
+        Ok(())
+    }
+}
+
+// leaf hash of an optional record, absent records are the empty leaf.
+pub(crate) fn leaf_hash(leaf: Option<Leaf>) -> Digest {
+    leaf.map(|leaf| hash_leaf(&leaf)).unwrap_or(Digest::ZERO)
+}
+
+// checks one mutation against the rules of its table, then swaps its leaf.
+fn apply_action<A: Action>(
+    root: &mut SubStateRoot,
+    action: &A,
+    proof: &MerkleProof,
+    before: Option<&A::Record>,
+    after: Option<&A::Record>
+) -> Result<(), String> {
+    action.process(before, after)?;
+    if before.into_iter().chain(after).any(|r| slot(r.id()) != Some(proof.index)) {
+        return Err(format!("{:?} record is not at its slot", A::TABLE));
+    }
+    root.apply(
+        proof,
+        &leaf_hash(before.map(|r| r.leaf())),
+        &leaf_hash(after.map(|r| r.leaf()))
+    )
+}
+
+// A single mutation of one row. `proof` is the path of the slot of the row in its table as it
+// is *before* this mutation (so after every earlier mutation in the batch).
+// `before` is None for CREATE, `after` is None for DELETE. Records are boxed, they are far
+// larger than the rest of a transition.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Transition {
+    OilToken {
+        action: OilTokenAction,
+        proof: MerkleProof,
+        before: Option<Box<OilTokenRecord>>,
+        after: Option<Box<OilTokenRecord>>,
+    },
+    Tokenization {
+        action: TokenizationAction,
+        proof: MerkleProof,
+        before: Option<Box<TokenizationRecord>>,
+        after: Option<Box<TokenizationRecord>>,
+    },
+    Comment {
+        action: CommentAction,
+        proof: MerkleProof,
+        before: Option<Box<CommentRecord>>,
+        after: Option<Box<CommentRecord>>,
+    },
+}
+
+// Roots of the three committed tables, the state a block commits to.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TableRoots {
+    pub oil_token: SubStateRoot,
+    pub tokenization: SubStateRoot,
+    pub comment: SubStateRoot,
+}
+
+impl TableRoots {
+    pub fn root(&self) -> Digest {
+        OverallState::compute_root(&self.oil_token.root, &self.tokenization.root, &self.comment.root)
+    }
+
+    pub fn table(&self, table: StateTable) -> &SubStateRoot {
+        match table {
+            StateTable::OilToken => &self.oil_token,
+            StateTable::Tokenization => &self.tokenization,
+            StateTable::Comment => &self.comment,
+        }
+    }
+
+    pub fn table_mut(&mut self, table: StateTable) -> &mut SubStateRoot {
+        match table {
+            StateTable::OilToken => &mut self.oil_token,
+            StateTable::Tokenization => &mut self.tokenization,
+            StateTable::Comment => &mut self.comment,
+        }
+    }
+
+    /// Moves these roots, which must be those of `parent`, forward with `update` and links
+    /// the resulting state as the child block of `parent`.
+    pub(crate) fn advance<F>(
+        mut self,
+        parent: &BlockHeader,
+        timestamp: i64,
+        update: F
+    ) -> Result<(StateLink, BlockHeader, TableRoots), String>
+        where F: FnOnce(&mut TableRoots) -> Result<(), String>
+    {
+        if self.root() != parent.root {
+            return Err("Sub-state roots do not match the parent block".to_string());
+        }
+        update(&mut self)?;
+        let link = StateLink {
+            old_state: parent.root,
+            new_state: self.root(),
+            previous_updated_at: parent.updated_at,
+            updated_at: timestamp,
+        };
+        let block = parent.child(&link)?;
+        Ok((link, block, self))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TransitionParams {
+    pub parent: BlockHeader,
+    pub timestamp: i64,
+    pub roots: TableRoots,
+    pub actor: Actor,
+    pub actions: Vec<Transition>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TransitionState {
+    pub link: StateLink,
+    pub block: BlockHeader,
+    pub roots: TableRoots,
+    pub actor: Actor,
+    pub actions: u32,
+}
+
+impl TransitionParams {
+    /// Checks every action against its business rules and Merkle path,
+    /// and returns the resulting `old_state -> new_state` commitment.
+    pub fn apply(self) -> Result<TransitionState, String> {
+        let (link, block, roots) = self.roots.advance(&self.parent, self.timestamp, |roots| {
+            for transition in &self.actions {
+                match transition {
+                    Transition::OilToken { action, proof, before, after } => {
+                        apply_action(
+                            &mut roots.oil_token,
+                            action,
+                            proof,
+                            before.as_deref(),
+                            after.as_deref()
+                        )?;
+                    }
+                    Transition::Tokenization { action, proof, before, after } => {
+                        apply_action(
+                            &mut roots.tokenization,
+                            action,
+                            proof,
+                            before.as_deref(),
+                            after.as_deref()
+                        )?;
+                    }
+                    Transition::Comment { action, proof, before, after } => {
+                        apply_action(
+                            &mut roots.comment,
+                            action,
+                            proof,
+                            before.as_deref(),
+                            after.as_deref()
+                        )?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+
+        Ok(TransitionState {
+            link,
+            block,
+            roots,
+            actor: self.actor,
+            actions: self.actions.len() as u32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MerkleTree;
+    use alloc::vec;
+
+    fn hashes(count: u8) -> Vec<Digest> {
+        (0..count).map(|i| hash_leaf(&[i])).collect()
+    }
+
+    fn table(leaves: &[Digest]) -> SubStateRoot {
+        SubStateRoot {
+            root: MerkleTree::from_hashes(leaves.to_vec()).root(),
+            leaves: leaves
+                .iter()
+                .filter(|leaf| **leaf != Digest::ZERO)
+                .count() as u64,
+        }
+    }
+
+    #[test]
+    fn delete_empties_the_slot_along_its_path() {
+        let leaves = hashes(4);
+        let mut root = table(&leaves);
+        let proof = MerkleTree::from_hashes(leaves.clone()).proof(1).unwrap();
+        root.apply(&proof, &leaves[1], &Digest::ZERO).unwrap();
+        assert_eq!(root, table(&[leaves[0], Digest::ZERO, leaves[2], leaves[3]]));
+
+        // the slot takes a row again, and the last row goes away like the others
+        root.apply(&proof, &Digest::ZERO, &leaves[1]).unwrap();
+        assert_eq!(root, table(&leaves));
+        let proof = MerkleTree::from_hashes(leaves.clone()).proof(3).unwrap();
+        root.apply(&proof, &leaves[3], &Digest::ZERO).unwrap();
+        assert_eq!(root, table(&leaves[..3]));
+    }
+
+    #[test]
+    fn delete_of_another_leaf_is_refused() {
+        let leaves = hashes(3);
+        let mut root = table(&leaves);
+        let proof = MerkleTree::from_hashes(leaves.clone()).proof(0).unwrap();
+        assert!(root.apply(&proof, &leaves[1], &Digest::ZERO).is_err());
+        // nor can an empty slot be deleted
+        let empty = MerkleTree::from_hashes(leaves.clone()).proof(3).unwrap();
+        assert!(root.apply(&empty, &Digest::ZERO, &Digest::ZERO).is_err());
+        assert_eq!(root, table(&leaves));
+    }
+
+    #[test]
+    fn record_at_the_slot_of_another_id_is_refused() {
+        let record = CommentRecord {
+            id: 2,
+            oil_token_id: 1,
+            user: "0x01".into(),
+            content: "comment".into(),
+            created_at: 0,
+            updated_at: 0,
+        };
+        let mut root = table(&[]);
+        let tree = MerkleTree::from_hashes(vec![]);
+        let create = |root: &mut SubStateRoot, index| {
+            apply_action(root, &CommentAction::CREATE, &tree.proof(index).unwrap(), None, Some(&record))
+        };
+        assert!(create(&mut root, 0).is_err());
+        create(&mut root, 1).unwrap();
+        assert_eq!(root.leaves, 1);
+    }
+}
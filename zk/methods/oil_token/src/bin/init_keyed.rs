@@ -0,0 +1,13 @@
+use risc0_zkvm::guest::env;
+use oil_tokenization_core::{ KeyedParams, KeyedState };
+
+fn main() {
+    // Read the rows of the three tables keyed by primary key
+    let params: KeyedParams = env::read();
+
+    // Build the sparse Merkle roots
+    let keyed_state = KeyedState::init(&params).expect("Invalid keyed state");
+
+    // Write the keyed state to the journal
+    env::commit(&keyed_state);
+}
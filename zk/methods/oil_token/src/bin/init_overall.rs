@@ -1,17 +1,36 @@
-use risc0_zkvm::guest::env;
-use oil_tokenization_core::{ OverallState, OverallParams };
+use risc0_zkvm::{ guest::env, serde::to_vec, sha::Digest };
+use oil_tokenization_core::{ Action, OverallParams, OverallState, SubState };
+
+// Checks `state` against the journal of its init_state receipt, which the host
+// passed in as an assumption. The journal is the table followed by the state.
+fn verify_sub_state<A: Action>(state_image_id: Digest, state: &SubState<A>) {
+    let mut journal = to_vec(&A::TABLE).expect("Failed to encode table");
+    journal.extend(to_vec(state).expect("Failed to encode sub-state"));
+    env::verify(state_image_id, &journal).expect("Sub-state receipt does not verify");
+}
 
 fn main() {
-    // Read the input from the host
+    // Read the three proven sub-states from the host
     let params: OverallParams = env::read();
 
-    // Initialize the overall state
-    let overall_state = OverallState::init(
-        params.oil_token_leaves,
-        params.tokenization_leaves,
-        params.comment_leaves
+    // Compose the sub-state receipts instead of re-hashing every table
+    verify_sub_state(params.state_image_id, &params.oil_token_state);
+    verify_sub_state(params.state_image_id, &params.tokenization_state);
+    verify_sub_state(params.state_image_id, &params.comment_state);
+
+    let mut overall_state = OverallState::compose(
+        params.oil_token_state,
+        params.tokenization_state,
+        params.comment_state,
+        params.timestamp
     );
 
-    // Write the state to the journal
+    // Continue the chain from the parent block, if any
+    if let Some(parent) = params.parent {
+        overall_state = overall_state.follow(&parent).expect("Invalid parent block");
+    }
+
+    // Write the sub-state image and the overall state to the journal
+    env::commit(&params.state_image_id);
     env::commit(&overall_state);
 }
@@ -0,0 +1,23 @@
+use risc0_zkvm::guest::env;
+use oil_tokenization_core::{
+    CommentState,
+    OilTokenState,
+    StateTable,
+    SubStateParams,
+    TokenizationState,
+};
+
+fn main() {
+    // Read the table and its leaves from the host
+    let params: SubStateParams = env::read();
+
+    // Tag the journal with the table so a receipt cannot pass as another table's state
+    env::commit(&params.table);
+
+    // Initialize the state of that table and write it to the journal
+    match params.table {
+        StateTable::OilToken => env::commit(&OilTokenState::init(params.leaves)),
+        StateTable::Tokenization => env::commit(&TokenizationState::init(params.leaves)),
+        StateTable::Comment => env::commit(&CommentState::init(params.leaves)),
+    }
+}
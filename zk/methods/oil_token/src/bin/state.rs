@@ -1,13 +0,0 @@
-use risc0_zkvm::guest::env;
-use oil_tokenization_core::OilTokenizationState;
-
-fn main() {
-    // Read the input from the host
-    let state: OilTokenizationState = env::read();
-
-    // Process the state (placeholder for future state operations)
-    // This could include state validation, transitions, etc.
-
-    // Write the processed state to the journal
-    env::commit(&state);
-}
@@ -0,0 +1,13 @@
+use risc0_zkvm::guest::env;
+use oil_tokenization_core::DeltaParams;
+
+fn main() {
+    // Read the parent block, its sub-state roots and the changed leaves from the host
+    let params: DeltaParams = env::read();
+
+    // Recompute the roots from the Merkle paths of the changed leaves only
+    let delta_state = params.apply().expect("Invalid delta");
+
+    // Write old_state -> new_state to the journal
+    env::commit(&delta_state);
+}
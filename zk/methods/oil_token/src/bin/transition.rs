@@ -0,0 +1,13 @@
+use risc0_zkvm::guest::env;
+use oil_tokenization_core::TransitionParams;
+
+fn main() {
+    // Read the parent block, its sub-state roots and the actions from the host
+    let params: TransitionParams = env::read();
+
+    // Check every action against its business rules and Merkle path
+    let transition_state = params.apply().expect("Invalid state transition");
+
+    // Write old_state -> new_state to the journal
+    env::commit(&transition_state);
+}
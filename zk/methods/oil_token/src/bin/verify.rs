@@ -1,20 +1,12 @@
 use risc0_zkvm::guest::env;
-use oil_tokenization_core::{ VerifyParams, VerifyCommit };
+use oil_tokenization_core::VerifyParams;
 
 fn main() {
-    // Read the input from the host
+    // Read the signed message, the signature and the claimed address from the host
     let params: VerifyParams = env::read();
 
-    // Perform verification logic here
-    // This is a placeholder for actual verification implementation
-    let verified = true; // Replace with actual verification logic
-
-    let commit = VerifyCommit {
-        verified,
-        address: params.expected_addr,
-        timestamp: params.timestamp,
-        username: params.username,
-    };
+    // Recover the signer with the scheme the wallet signed with and compare it to the claimed address
+    let commit = params.verify();
 
     // Write the verification result to the journal
     env::commit(&commit);
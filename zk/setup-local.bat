@@ -13,7 +13,10 @@ if not exist .env (
         echo.
         echo # Server Configuration
         echo RUST_LOG=info
-        echo RISC0_DEV_MODE=1
+        echo.
+        echo # Dev mode proves with fake receipts, uncomment both for fast local proving, never in production
+        echo # RISC0_DEV_MODE=1
+        echo # ACCEPT_DEV_RECEIPTS=1
         echo.
         echo # JWT Secret (generate a secure secret for production)
         echo JWT_SECRET=your-super-secret-jwt-key-change-this-in-production
@@ -14,7 +14,10 @@ REDIS_URL=redis://localhost:6379
 
 # Server Configuration
 RUST_LOG=info
-RISC0_DEV_MODE=1
+
+# Dev mode proves with fake receipts, uncomment both for fast local proving, never in production
+# RISC0_DEV_MODE=1
+# ACCEPT_DEV_RECEIPTS=1
 
 # JWT Secret (generate a secure secret for production)
 JWT_SECRET=your-super-secret-jwt-key-change-this-in-production
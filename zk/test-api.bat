@@ -52,6 +52,49 @@ call :test_endpoint "GET" "/api/cars/init" "Initialize cars"
 call :test_endpoint "GET" "/api/auctions/init" "Initialize auctions"
 call :test_endpoint "GET" "/api/bids/init" "Initialize bids"
 
+REM Signed writes need foundry's cast, a session TOKEN and the PRIVATE_KEY of its wallet
+echo === Testing Signed Writes ===
+if not defined CHAIN_ID set CHAIN_ID=1
+where cast >nul 2>nul
+if errorlevel 1 goto :skip_signed
+if "%TOKEN%"=="" goto :skip_signed
+if "%PRIVATE_KEY%"=="" goto :skip_signed
+
+echo Testing: Create a signed comment
+echo Endpoint: POST %BASE_URL%/api/comments
+set BODY_FILE=%TEMP%\action-body.json
+set TYPED_FILE=%TEMP%\action-typed-data.json
+<nul set /p ="{"id":0,"oil_token_id":1,"user":"","content":"hello from test-api.bat","created_at":"2024-01-01T00:00:00","updated_at":"2024-01-01T00:00:00"}" > "%BODY_FILE%"
+for /f "tokens=*" %%i in ('cast wallet address --private-key %PRIVATE_KEY%') do set ACTOR=%%i
+for /f "tokens=*" %%i in ('powershell -NoProfile -Command "'%ACTOR%'.ToLower()"') do set ACTOR=%%i
+for /f "tokens=*" %%i in ('powershell -NoProfile -Command "[guid]::NewGuid().ToString('N')"') do set NONCE=%%i
+for /f "tokens=*" %%i in ('powershell -NoProfile -Command "[DateTimeOffset]::UtcNow.ToUnixTimeSeconds() + 300"') do set DEADLINE=%%i
+for /f "tokens=*" %%i in ('type "%BODY_FILE%" ^| cast keccak') do set CONTENT=%%i
+(
+echo {"types":{"EIP712Domain":[{"name":"name","type":"string"},{"name":"version","type":"string"},{"name":"chainId","type":"uint256"}],
+echo "MarketAction":[{"name":"actor","type":"address"},{"name":"table","type":"string"},{"name":"action","type":"string"},{"name":"content","type":"bytes32"},{"name":"nonce","type":"string"},{"name":"deadline","type":"uint256"}]},
+echo "primaryType":"MarketAction","domain":{"name":"Oil Tokenization Platform","version":"1","chainId":%CHAIN_ID%},
+echo "message":{"actor":"%ACTOR%","table":"comment","action":"CREATE","content":"%CONTENT%","nonce":"%NONCE%","deadline":%DEADLINE%}}
+) > "%TYPED_FILE%"
+for /f "tokens=*" %%i in ('cast wallet sign --data --from-file --private-key %PRIVATE_KEY% "%TYPED_FILE%"') do set SIGNATURE=%%i
+
+curl -s -w " %%{http_code}" -X POST "%BASE_URL%/api/comments" ^
+    -H "Content-Type: application/json" ^
+    -H "Authorization: Bearer %TOKEN%" ^
+    -H "x-action-signature: %SIGNATURE%" ^
+    -H "x-action-nonce: %NONCE%" ^
+    -H "x-action-deadline: %DEADLINE%" ^
+    --data-binary "@%BODY_FILE%"
+echo.
+echo (202 = queued)
+echo.
+goto :signed_done
+
+:skip_signed
+echo ⏭️  Skipped: set TOKEN and PRIVATE_KEY and install foundry's cast to test signed writes
+echo.
+
+:signed_done
 echo ✅ API testing complete!
 echo.
 echo 📋 Summary:
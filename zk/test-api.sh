@@ -55,6 +55,86 @@ test_endpoint "GET" "/api/cars/init" "Initialize cars"
 test_endpoint "GET" "/api/auctions/init" "Initialize auctions"
 test_endpoint "GET" "/api/bids/init" "Initialize bids"
 
+# Signs a marketplace write as an EIP-712 MarketAction and POSTs it. Needs foundry's
+# `cast`, a session TOKEN and the PRIVATE_KEY of the wallet it was issued to.
+test_signed_write() {
+    local endpoint=$1
+    local table=$2
+    local action=$3
+    local body=$4
+    local description=$5
+
+    echo "Testing: $description"
+    echo "Endpoint: POST $BASE_URL$endpoint"
+
+    local actor nonce deadline content typed_data signature
+    actor=$(cast wallet address --private-key "$PRIVATE_KEY" | tr '[:upper:]' '[:lower:]')
+    nonce=$(openssl rand -hex 16)
+    deadline=$(($(date +%s) + 300))
+    content=$(cast keccak "$body")
+    typed_data=$(cat <<JSON
+{
+  "types": {
+    "EIP712Domain": [
+      { "name": "name", "type": "string" },
+      { "name": "version", "type": "string" },
+      { "name": "chainId", "type": "uint256" }
+    ],
+    "MarketAction": [
+      { "name": "actor", "type": "address" },
+      { "name": "table", "type": "string" },
+      { "name": "action", "type": "string" },
+      { "name": "content", "type": "bytes32" },
+      { "name": "nonce", "type": "string" },
+      { "name": "deadline", "type": "uint256" }
+    ]
+  },
+  "primaryType": "MarketAction",
+  "domain": { "name": "Oil Tokenization Platform", "version": "1", "chainId": $CHAIN_ID },
+  "message": {
+    "actor": "$actor",
+    "table": "$table",
+    "action": "$action",
+    "content": "$content",
+    "nonce": "$nonce",
+    "deadline": $deadline
+  }
+}
+JSON
+)
+    signature=$(cast wallet sign --data --private-key "$PRIVATE_KEY" "$typed_data")
+
+    response=$(curl -s -w "\n%{http_code}" -X POST "$BASE_URL$endpoint" \
+        -H "Content-Type: application/json" \
+        -H "Authorization: Bearer $TOKEN" \
+        -H "x-action-signature: $signature" \
+        -H "x-action-nonce: $nonce" \
+        -H "x-action-deadline: $deadline" \
+        --data-raw "$body")
+
+    status_code=$(echo "$response" | tail -n1)
+    body=$(echo "$response" | head -n -1)
+
+    if [ "$status_code" = "202" ]; then
+        echo "✅ Status: $status_code"
+        echo "📄 Response: $(echo "$body" | head -c 100)..."
+    else
+        echo "❌ Status: $status_code"
+        echo "📄 Response: $body"
+    fi
+    echo ""
+}
+
+echo "=== Testing Signed Writes ==="
+CHAIN_ID=${CHAIN_ID:-1}
+if [ -z "$TOKEN" ] || [ -z "$PRIVATE_KEY" ] || ! command -v cast > /dev/null; then
+    echo "⏭️  Skipped: set TOKEN and PRIVATE_KEY and install foundry's cast to test signed writes"
+    echo ""
+else
+    test_signed_write "/api/comments" "comment" "CREATE" \
+        '{"id":0,"oil_token_id":1,"user":"","content":"hello from test-api.sh","created_at":"2024-01-01T00:00:00","updated_at":"2024-01-01T00:00:00"}' "Create a signed comment"
+fi
+
 echo "✅ API testing complete!"
 echo ""
 echo "📋 Summary:"